@@ -1,151 +1,924 @@
+mod scene;
+mod animation;
+mod material;
+mod export;
+
+pub use scene::{Node, Scene};
+pub use animation::{Skin, Animation, Channel, Interpolation, Values, Pose};
+pub use material::{Material, AlphaMode, TextureInfo, TextureTransform, Texture, Sampler, Filter, Wrap, Image, ImageSource, Transmission, Volume, Specular, Clearcoat, Sheen};
+
 use gltf::*;
 use crate::math::{Vec2, Vec3, Vec4};
-use std::ops::Range;
-
-#[derive(Debug)]
-pub enum TextureOrConstant<const N: usize>
-{
-    Texture(String),
-    Constant([f32; N])
-}
+use std::{ops::Range, result::Result};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Mesh
 {
     pub name: String,
     pub vertices: Range<usize>,
     pub indices: Range<usize>,
-    pub diffuse_texture: TextureOrConstant<4>,
-    pub normal_texture: Option<String>,
-    pub roughness_texture: TextureOrConstant<1>
+    pub material: Option<usize> //index into Model::materials, None is Material::default()
 }
 
 #[derive(Debug)]
+pub enum Error
+{
+    Gltf(gltf::Error),
+    MissingBlob, //a buffer refers to the GLB binary chunk, but there is none
+    DataUri, //malformed or not base64 encoded
+    UnresolvedUri(String), //the callback could not provide the buffer
+    BufferLength(usize), //buffer is shorter than declared
+    UnnamedMesh,
+    NoPositions(String),
+    NoIndices(String),
+    AttributeCounts(String), //attributes are shared by all meshes
+    DataType(accessor::DataType),
+    OutOfBounds,
+    Hierarchy, //a node has several parents or the nodes form a cycle
+    Keyframes, //animation sampler output does not match its input
+    InverseBindMatrices, //not exactly one per joint
+    Json(json::Error), //encoding only
+    EmptyMesh(String), //no vertices or no indices, glTF accessors cannot be empty
+    NodeMeshes(usize), //the meshes of the node are not exactly one glTF mesh
+    NonFinite(&'static str), //NaN or infinity, which json cannot represent
+    AttributeLength(&'static str), //not one element per position
+    MeshVertices(String), //the vertex range is out of bounds
+    MeshIndices(String), //the index range is out of bounds or an index is not below the vertex count of the mesh
+    MeshMaterial(String) //the material index is out of bounds
+}
+
+impl std::fmt::Display for Error
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self
+        {
+            Self::Gltf(err) => write!(f, "{err}"),
+            Self::MissingBlob => write!(f, "Missing GLB binary chunk"),
+            Self::DataUri => write!(f, "Invalid data URI"),
+            Self::UnresolvedUri(uri) => write!(f, "Could not resolve buffer URI {uri}"),
+            Self::BufferLength(index) => write!(f, "Buffer {index} is shorter than declared"),
+            Self::UnnamedMesh => write!(f, "Mesh has no name"),
+            Self::NoPositions(name) => write!(f, "Mesh {name} has no positions"),
+            Self::NoIndices(name) => write!(f, "Mesh {name} has no indices"),
+            Self::AttributeCounts(name) => write!(f, "Mesh {name} does not have the same attributes as the previous meshes"),
+            Self::DataType(data_type) => write!(f, "Unsupported data type {data_type:?}"),
+            Self::OutOfBounds => write!(f, "Accessor out of bounds"),
+            Self::Hierarchy => write!(f, "Nodes do not form a tree"),
+            Self::Keyframes => write!(f, "Animation sampler output does not match its input"),
+            Self::InverseBindMatrices => write!(f, "Skin does not have one inverse bind matrix per joint"),
+            Self::Json(err) => write!(f, "{err}"),
+            Self::EmptyMesh(name) => write!(f, "Mesh {name} has no vertices or no indices"),
+            Self::NodeMeshes(index) => write!(f, "The meshes of node {index} do not form a glTF mesh"),
+            Self::NonFinite(what) => write!(f, "The {what} are not finite"),
+            Self::AttributeLength(what) => write!(f, "The {what} do not have one element per position"),
+            Self::MeshVertices(name) => write!(f, "Mesh {name} has vertices out of bounds"),
+            Self::MeshIndices(name) => write!(f, "Mesh {name} has indices out of bounds"),
+            Self::MeshMaterial(name) => write!(f, "Mesh {name} has a material out of bounds")
+        }
+    }
+}
+
+impl std::error::Error for Error
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)>
+    {
+        match self
+        {
+            Self::Gltf(err) => Some(err),
+            Self::Json(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct Model
 {
     pub positions: Vec<Vec3>,
     pub normals: Option<Vec<Vec3>>,
-    pub tangents: Option<Vec<Vec3>>,
+    pub tangents: Option<Vec<Vec4>>, //w is the handedness
     pub tex_coords: Option<Vec<Vec2>>,
+    pub tex_coords_1: Option<Vec<Vec2>>, //second uv set
+    pub colors: Option<Vec<Vec4>>, //rgba, alpha is 1 for rgb colors
+    pub joints: Option<Vec<[u16; 4]>>, //indices into Skin::joints
+    pub weights: Option<Vec<Vec4>>,
     pub indices: Vec<u32>,
-    pub meshes: Vec<Mesh>
+    pub meshes: Vec<Mesh>,
+    pub nodes: Vec<Node>,
+    pub scenes: Vec<Scene>,
+    pub scene: Option<usize>, //default scene
+    pub skins: Vec<Skin>,
+    pub animations: Vec<Animation>,
+    pub materials: Vec<Material>,
+    pub textures: Vec<Texture>,
+    pub images: Vec<Image>
 }
 
 impl Model
 {
+    //gltf can be .gltf or .glb, bin is used for every external buffer
     pub fn decode(gltf: &[u8], bin: &[u8]) -> Self
     {
-        let Gltf { document: doc, blob: None } = Gltf::from_slice(gltf).unwrap() else { panic!("Binary glTF") };
+        Self::try_decode(gltf, bin).unwrap_or_else(|err| panic!("Model::decode: {err}"))
+    }
+
+    pub fn try_decode(gltf: &[u8], bin: &[u8]) -> Result<Self, Error>
+    {
+        Self::try_load(gltf, |_| Some(bin.to_vec()))
+    }
+
+    //gltf can be .gltf or .glb, resolve is called with the URI of every external buffer
+    pub fn load(gltf: &[u8], resolve: impl FnMut(&str) -> Option<Vec<u8>>) -> Self
+    {
+        Self::try_load(gltf, resolve).unwrap_or_else(|err| panic!("Model::load: {err}"))
+    }
+
+    pub fn try_load(gltf: &[u8], mut resolve: impl FnMut(&str) -> Option<Vec<u8>>) -> Result<Self, Error>
+    {
+        let Gltf { document: doc, mut blob } = Gltf::from_slice(gltf).map_err(Error::Gltf)?;
+
+        let buffers = Buffers(doc.buffers().map(|buffer|
+        {
+            let data = match buffer.source()
+            {
+                buffer::Source::Bin => blob.take().ok_or(Error::MissingBlob)?,
+                buffer::Source::Uri(uri) if uri.starts_with("data:") => decode_data_uri(uri)?,
+                buffer::Source::Uri(uri) => resolve(uri).ok_or_else(|| Error::UnresolvedUri(uri.to_owned()))?
+            };
+            if data.len() < buffer.length() { return Err(Error::BufferLength(buffer.index())); }
+            Ok(data)
+        }).collect::<Result<_, _>>()?);
 
         let mut positions = Vec::new();
         let mut normals = Vec::new();
         let mut tangents = Vec::new();
         let mut tex_coords = Vec::new();
+        let mut tex_coords_1 = Vec::new();
+        let mut colors = Vec::new();
+        let mut joints = Vec::new();
+        let mut weights = Vec::new();
         let mut indices = Vec::new();
 
-        let meshes = doc.meshes().map(|mesh|
+        //every primitive is its own mesh, the primitives of a glTF mesh are consecutive
+        let mut meshes = Vec::new();
+        let mut primitives = Vec::new();
+        for mesh in doc.meshes()
         {
-            let name = mesh.name().unwrap().to_owned();
-            let mut primitives = mesh.primitives();
-            let primitive = primitives.next().unwrap();
-            if primitives.next().is_some() { panic!("Mesh {} has more than 1 primitive", name); }
-            
-            let vertex_start = positions.len() / 3;
-            for attribute in primitive.attributes()
+            let name = mesh.name().ok_or(Error::UnnamedMesh)?;
+            let start = meshes.len();
+            for primitive in mesh.primitives()
             {
-                if let Some(vec) = match attribute.0
+                let name = name.to_owned();
+                let vertex_start = positions.len() / 3;
+                for (semantic, accessor) in primitive.attributes()
                 {
-                    Semantic::Positions => Some(&mut positions),
-                    Semantic::Normals => Some(&mut normals),
-                    Semantic::Tangents => Some(&mut tangents),
-                    Semantic::TexCoords(_) => Some(&mut tex_coords),
-                    _ => None
-                } {
-                    let accessor = attribute.1;
-                    if accessor.data_type() != accessor::DataType::F32 { panic!("No F32 data"); }
-                    let view = accessor.view().unwrap();
-                    let data = &bin[view.offset()..];
-                    let stride = view.stride().unwrap_or_else(|| accessor.size());
-                    for i in 0..accessor.count()
+                    match semantic
                     {
-                        let start = (stride * i) + accessor.offset();
-                        let data = &data[start..(start + accessor.size())];
-                        for [f1, f2, f3, f4] in data.iter().array_chunks()
+                        Semantic::Positions => positions.extend(buffers.floats(&accessor)?),
+                        Semantic::Normals => normals.extend(buffers.floats(&accessor)?),
+                        Semantic::Tangents => tangents.extend(buffers.floats(&accessor)?),
+                        Semantic::TexCoords(0) => tex_coords.extend(buffers.floats(&accessor)?),
+                        Semantic::TexCoords(1) => tex_coords_1.extend(buffers.floats(&accessor)?),
+                        Semantic::Colors(0) => match accessor.dimensions()
                         {
-                            let float = [*f1, *f2, *f3, *f4];
-                            vec.push(f32::from_le_bytes(float));
-                        }
+                            accessor::Dimensions::Vec3 => colors.extend(buffers.floats(&accessor)?.into_iter().array_chunks().flat_map(|[r, g, b]| [r, g, b, 1.0])),
+                            _ => colors.extend(buffers.floats(&accessor)?)
+                        },
+                        Semantic::Joints(0) => joints.extend(buffers.ints(&accessor)?.into_iter().map(|int| int as u16)),
+                        Semantic::Weights(0) => weights.extend(buffers.floats(&accessor)?),
+                        _ => {}
                     }
                 }
-            }
-            let vertex_end = positions.len() / 3;
-            assert!(vertex_end > vertex_start);
-
-            //all meshes have to share the same data
-            let num_vertices = positions.len() / 3;
-            assert!(normals.len() == 0 || normals.len() / 3 == num_vertices);
-            assert!(tangents.len() == 0 || tangents.len() / 4 == num_vertices);
-            assert!(tex_coords.len() == 0 || tex_coords.len() / 2 == num_vertices);
-
-            let index_start = indices.len();
-            let accessor = primitive.indices().unwrap();
-            let view = accessor.view().unwrap();
-            let data = &bin[view.offset()..];
-            let stride = view.stride().unwrap_or_else(|| accessor.size());
-            for i in 0..accessor.count()
-            {
-                let start = (stride * i) + accessor.offset();
-                let data = &data[start..(start + accessor.size())];
-                match accessor.data_type()
+                let vertex_end = positions.len() / 3;
+                if vertex_end <= vertex_start { return Err(Error::NoPositions(name)); }
+
+                //all meshes have to share the same data
+                let num_vertices = positions.len() / 3;
+                if (!normals.is_empty() && normals.len() / 3 != num_vertices)
+                 || (!tangents.is_empty() && tangents.len() / 4 != num_vertices)
+                 || (!tex_coords.is_empty() && tex_coords.len() / 2 != num_vertices)
+                 || (!tex_coords_1.is_empty() && tex_coords_1.len() / 2 != num_vertices)
+                 || (!colors.is_empty() && colors.len() / 4 != num_vertices)
+                 || (!joints.is_empty() && joints.len() / 4 != num_vertices)
+                 || (!weights.is_empty() && weights.len() / 4 != num_vertices)
+                { return Err(Error::AttributeCounts(name)); }
+
+                let index_start = indices.len();
+                let Some(accessor) = primitive.indices() else { return Err(Error::NoIndices(name)) };
+                indices.extend(buffers.ints(&accessor)?);
+                let index_end = indices.len();
+
+                meshes.push(Mesh
                 {
-                    accessor::DataType::U16 => for int in data.chunks_exact(2) { indices.push(u16::from_le_bytes(int.try_into().unwrap()) as u32); },
-                    accessor::DataType::U32 => for int in data.chunks_exact(4) { indices.push(u32::from_le_bytes(int.try_into().unwrap())); },
-                    _ => unreachable!()
-                }
+                    name,
+                    vertices: vertex_start..vertex_end,
+                    indices: index_start..index_end,
+                    material: primitive.material().index()
+                });
             }
-            let index_end = indices.len();
-            
-            let material = primitive.material();
-            let diffuse_texture = match material.pbr_metallic_roughness().base_color_texture().map(|tex| tex.texture().source().source())
-            {
-                Some(image::Source::Uri { uri, .. }) => TextureOrConstant::Texture(uri.to_owned()),
-                _ => TextureOrConstant::Constant(material.pbr_metallic_roughness().base_color_factor())
-            };
-            let normal_texture = match material.normal_texture().map(|tex| tex.texture().source().source())
-            {
-                Some(image::Source::Uri { uri, .. }) => Some(uri.to_owned()),
-                _ => None
-            };
-            let roughness_texture = match material.pbr_metallic_roughness().metallic_roughness_texture().map(|tex| tex.texture().source().source())
-            {
-                Some(image::Source::Uri { uri, .. }) => TextureOrConstant::Texture(uri.to_owned()),
-                _ => TextureOrConstant::Constant([material.pbr_metallic_roughness().roughness_factor()])
-            };
+            primitives.push(start..meshes.len());
+        }
 
-            Mesh
-            {
-                name,
-                vertices: vertex_start..vertex_end,
-                indices: index_start..index_end,
-                diffuse_texture,
-                normal_texture,
-                roughness_texture
-            }
-        }).collect();
+        let (nodes, scenes) = scene::decode(&doc, &primitives)?;
+        let scene = doc.default_scene().map(|scene| scene.index());
+        let skins = doc.skins().map(|skin| animation::decode_skin(&skin, &buffers)).collect::<Result<_, _>>()?;
+        let animations = doc.animations().map(|anim| animation::decode_animation(&anim, &buffers)).collect::<Result<_, _>>()?;
+        let materials = doc.materials().map(|material| material::decode_material(&material)).collect();
+        let textures = doc.textures().map(|texture| material::decode_texture(&texture)).collect();
+        let images = doc.images().map(|image| material::decode_image(&image, &buffers)).collect::<Result<_, _>>()?;
 
         let positions = positions.iter().array_chunks().map(|[x, y, z]| Vec3(*x, *y, *z)).collect();
         let normals = if normals.len() != 0 { Some(normals.iter().array_chunks().map(|[x, y, z]| Vec3(*x, *y, *z)).collect()) } else { None };
-        let tangents = if tangents.len() != 0 { Some(tangents.iter().array_chunks().map(|[x, y, z, w]| Vec4(*x, *y, *z, *w).without_w()).collect()) } else { None };
+        let tangents = if tangents.len() != 0 { Some(tangents.iter().array_chunks().map(|[x, y, z, w]| Vec4(*x, *y, *z, *w)).collect()) } else { None };
         let tex_coords = if tex_coords.len() != 0 { Some(tex_coords.iter().array_chunks().map(|[x, y]| Vec2(*x, *y)).collect()) } else { None };
+        let tex_coords_1 = if !tex_coords_1.is_empty() { Some(tex_coords_1.into_iter().array_chunks().map(|[x, y]| Vec2(x, y)).collect()) } else { None };
+        let colors = if !colors.is_empty() { Some(colors.into_iter().array_chunks().map(Vec4::from).collect()) } else { None };
+        let joints = if !joints.is_empty() { Some(joints.into_iter().array_chunks().collect()) } else { None };
+        let weights = if !weights.is_empty() { Some(weights.into_iter().array_chunks().map(Vec4::from).collect()) } else { None };
 
-        Self
+        Ok(Self
         {
             positions,
             normals,
             tangents,
             tex_coords,
+            tex_coords_1,
+            colors,
+            joints,
+            weights,
             indices,
-            meshes
+            meshes,
+            nodes,
+            scenes,
+            scene,
+            skins,
+            animations,
+            materials,
+            textures,
+            images
+        })
+    }
+}
+
+impl Model
+{
+    //fills tangents via math::tangents, returns false without normals or uvs
+    //if vertices are split on mirrored uv seams, the vertices are rebuilt mesh by mesh and the vertex ranges change
+    pub fn generate_tangents(&mut self) -> bool
+    {
+        let (Some(normals), Some(tex_coords)) = (&self.normals, &self.tex_coords) else { return false };
+        let results: Vec<_> = self.meshes.iter().map(|mesh|
+        {
+            let vertices = mesh.vertices.clone();
+            crate::math::tangents(&self.positions[vertices.clone()], &normals[vertices.clone()], &tex_coords[vertices], &self.indices[mesh.indices.clone()])
+        }).collect();
+
+        if results.iter().zip(&self.meshes).all(|((tangents, _, _), mesh)| tangents.len() == mesh.vertices.len())
+        {
+            let mut tangents = vec![Vec4::zero(); self.positions.len()];
+            for ((mesh_tangents, _, _), mesh) in results.iter().zip(&self.meshes) { tangents[mesh.vertices.clone()].copy_from_slice(mesh_tangents); }
+            self.tangents = Some(tangents);
+            return true;
+        }
+
+        let mut tangents = Vec::new();
+        let mut remap = Vec::new(); //the old vertex of every new one
+        for ((mesh_tangents, mesh_remap, mesh_indices), mesh) in results.into_iter().zip(&mut self.meshes)
+        {
+            self.indices[mesh.indices.clone()].copy_from_slice(&mesh_indices);
+            remap.extend(mesh_remap.into_iter().map(|i| mesh.vertices.start + i as usize));
+            mesh.vertices = tangents.len()..tangents.len() + mesh_tangents.len();
+            tangents.extend(mesh_tangents);
+        }
+        fn apply<T: Copy>(data: &mut Vec<T>, remap: &[usize]) { *data = remap.iter().map(|&i| data[i]).collect(); }
+        apply(&mut self.positions, &remap);
+        if let Some(normals) = &mut self.normals { apply(normals, &remap); }
+        for tex_coords in [&mut self.tex_coords, &mut self.tex_coords_1].into_iter().flatten() { apply(tex_coords, &remap); }
+        for data in [&mut self.colors, &mut self.weights].into_iter().flatten() { apply(data, &remap); }
+        if let Some(joints) = &mut self.joints { apply(joints, &remap); }
+        self.tangents = Some(tangents);
+        true
+    }
+}
+
+struct Buffers(Vec<Vec<u8>>);
+
+impl Buffers
+{
+    //the bytes of a buffer view from offset on
+    fn view(&self, view: &buffer::View, offset: usize) -> Result<&[u8], Error>
+    {
+        self.0[view.buffer().index()].get(view.offset()..(view.offset() + view.length())).and_then(|view| view.get(offset..)).ok_or(Error::OutOfBounds)
+    }
+
+    //all elements of an accessor tightly packed, with sparse substitution applied
+    fn elements(&self, accessor: &Accessor) -> Result<Vec<u8>, Error>
+    {
+        let size = accessor.size();
+        let mut elements = vec![0; accessor.count() * size];
+        if let Some(view) = accessor.view()
+        {
+            let data = self.view(&view, accessor.offset())?;
+            let stride = view.stride().unwrap_or(size);
+            for (i, element) in elements.chunks_exact_mut(size).enumerate()
+            {
+                element.copy_from_slice(data.get((stride * i)..(stride * i + size)).ok_or(Error::OutOfBounds)?);
+            }
+        }
+        if let Some(sparse) = accessor.sparse()
+        {
+            let (indices, values) = (sparse.indices(), sparse.values());
+            let index_data = self.view(&indices.view(), indices.offset())?;
+            let value_data = self.view(&values.view(), values.offset())?;
+            for i in 0..sparse.count()
+            {
+                let index = match indices.index_type()
+                {
+                    accessor::sparse::IndexType::U8 => index_data.get(i).map(|&int| int as usize),
+                    accessor::sparse::IndexType::U16 => index_data.get((2 * i)..(2 * i + 2)).map(|int| u16::from_le_bytes(int.try_into().unwrap()) as usize),
+                    accessor::sparse::IndexType::U32 => index_data.get((4 * i)..(4 * i + 4)).map(|int| u32::from_le_bytes(int.try_into().unwrap()) as usize)
+                }.ok_or(Error::OutOfBounds)?;
+                let value = value_data.get((size * i)..(size * i + size)).ok_or(Error::OutOfBounds)?;
+                elements.get_mut((size * index)..(size * index + size)).ok_or(Error::OutOfBounds)?.copy_from_slice(value);
+            }
+        }
+        Ok(elements)
+    }
+
+    //all components of all elements, integers have to be normalized
+    fn floats(&self, accessor: &Accessor) -> Result<Vec<f32>, Error>
+    {
+        let data_type = accessor.data_type();
+        if data_type != accessor::DataType::F32 && !accessor.normalized() { return Err(Error::DataType(data_type)); }
+        let data = self.elements(accessor)?;
+        Ok(match data_type
+        {
+            accessor::DataType::F32 => data.chunks_exact(4).map(|float| f32::from_le_bytes(float.try_into().unwrap())).collect(),
+            accessor::DataType::U8 => data.iter().map(|&int| int as f32 / u8::MAX as f32).collect(),
+            accessor::DataType::U16 => data.chunks_exact(2).map(|int| u16::from_le_bytes(int.try_into().unwrap()) as f32 / u16::MAX as f32).collect(),
+            accessor::DataType::I8 => data.iter().map(|&int| (int as i8 as f32 / i8::MAX as f32).max(-1.0)).collect(),
+            accessor::DataType::I16 => data.chunks_exact(2).map(|int| (i16::from_le_bytes(int.try_into().unwrap()) as f32 / i16::MAX as f32).max(-1.0)).collect(),
+            accessor::DataType::U32 => return Err(Error::DataType(data_type))
+        })
+    }
+
+    //all components of all elements of an unsigned integer accessor
+    fn ints(&self, accessor: &Accessor) -> Result<Vec<u32>, Error>
+    {
+        let data = self.elements(accessor)?;
+        Ok(match accessor.data_type()
+        {
+            accessor::DataType::U8 => data.iter().map(|&int| int as u32).collect(),
+            accessor::DataType::U16 => data.chunks_exact(2).map(|int| u16::from_le_bytes(int.try_into().unwrap()) as u32).collect(),
+            accessor::DataType::U32 => data.chunks_exact(4).map(|int| u32::from_le_bytes(int.try_into().unwrap())).collect(),
+            data_type => return Err(Error::DataType(data_type))
+        })
+    }
+}
+
+//data:[<mediatype>];base64,<data>
+fn decode_data_uri(uri: &str) -> Result<Vec<u8>, Error>
+{
+    let (header, data) = uri.strip_prefix("data:").and_then(|uri| uri.split_once(',')).ok_or(Error::DataUri)?;
+    if !header.ends_with(";base64") { return Err(Error::DataUri); }
+    let data = data.trim_end_matches('=').as_bytes();
+
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    let mut accumulator = 0u32;
+    let mut bits = 0;
+    for &c in data
+    {
+        let sextet = match c
+        {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return Err(Error::DataUri)
+        };
+        accumulator = (accumulator << 6) | sextet as u32;
+        bits += 6;
+        if bits >= 8
+        {
+            bits -= 8;
+            bytes.push((accumulator >> bits) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    //one triangle: 3 positions followed by 3 u16 indices and padding
+    fn triangle_bin() -> Vec<u8>
+    {
+        let mut bin = Vec::new();
+        for float in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] { bin.extend_from_slice(&float.to_le_bytes()); }
+        for int in [0u16, 1, 2, 0] { bin.extend_from_slice(&int.to_le_bytes()); }
+        bin
+    }
+
+    //extra gets inserted as top level properties
+    fn triangle_json(uri: Option<&str>, extra: &str) -> String
+    {
+        let uri = uri.map(|uri| format!(r#""uri": "{uri}", "#)).unwrap_or_default();
+        format!(r#"{{
+            "asset": {{ "version": "2.0" }},{extra}
+            "buffers": [{{ {uri}"byteLength": 44 }}],
+            "bufferViews":
+            [
+                {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                {{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }}
+            ],
+            "accessors":
+            [
+                {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] }},
+                {{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+            ],
+            "meshes": [{{ "name": "triangle", "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "indices": 1 }}] }}]
+        }}"#)
+    }
+
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8>
+    {
+        let mut json = json.as_bytes().to_vec();
+        while !json.len().is_multiple_of(4) { json.push(b' '); }
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+        let mut glb = Vec::new();
+        for int in [0x46546C67, 2, length as u32, json.len() as u32, 0x4E4F534A] { glb.extend_from_slice(&u32::to_le_bytes(int)); }
+        glb.extend_from_slice(&json);
+        for int in [bin.len() as u32, 0x004E4942] { glb.extend_from_slice(&u32::to_le_bytes(int)); }
+        glb.extend_from_slice(bin);
+        glb
+    }
+
+    fn check(model: &Model)
+    {
+        assert_eq!(model.positions, vec![Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0)]);
+        assert_eq!(model.indices, vec![0, 1, 2]);
+        assert_eq!(model.meshes.len(), 1);
+        assert_eq!(model.meshes[0].name, "triangle");
+    }
+
+    #[test]
+    fn buffers()
+    {
+        let bin = triangle_bin();
+
+        let json = triangle_json(Some("triangle.bin"), "");
+        check(&Model::load(json.as_bytes(), |uri| (uri == "triangle.bin").then(|| bin.clone())));
+        check(&Model::decode(json.as_bytes(), &bin));
+        assert!(matches!(Model::try_load(json.as_bytes(), |_| None), Err(Error::UnresolvedUri(uri)) if uri == "triangle.bin"));
+        assert!(matches!(Model::try_decode(json.as_bytes(), &bin[..40]), Err(Error::BufferLength(0))));
+
+        let json = triangle_json(Some("data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIAAAA="), "");
+        check(&Model::load(json.as_bytes(), |_| None));
+        let json = triangle_json(Some("data:application/octet-stream;base64,AAAA!AAA"), "");
+        assert!(matches!(Model::try_load(json.as_bytes(), |_| None), Err(Error::DataUri)));
+
+        let json = triangle_json(None, "");
+        check(&Model::load(&glb(&json, &bin), |_| None));
+        assert!(matches!(Model::try_load(json.as_bytes(), |_| None), Err(Error::MissingBlob)));
+    }
+
+    const NODES: &str = r#"
+        "nodes":
+        [
+            { "name": "root", "translation": [1, 0, 0], "children": [1] },
+            { "name": "child", "rotation": [0, 0, 0.70710678, 0.70710678], "scale": [2, 2, 2], "mesh": 0 },
+            { "matrix": [1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 3, 1] }
+        ],
+        "scenes": [{ "name": "scene", "nodes": [0, 2] }],
+        "scene": 0,"#;
+
+    #[test]
+    fn nodes()
+    {
+        let bin = triangle_bin();
+        let json = triangle_json(Some("triangle.bin"), NODES);
+        let model = Model::decode(json.as_bytes(), &bin);
+        assert_eq!(model.nodes.len(), 3);
+        assert_eq!(model.nodes[0].name.as_deref(), Some("root"));
+        assert_eq!(model.nodes[0].children, vec![1]);
+        assert_eq!(model.nodes[1].parent, Some(0));
+        assert_eq!(model.nodes[1].meshes, 0..1);
+        assert!(model.nodes[0].meshes.is_empty());
+        assert_eq!(model.nodes[2].name, None);
+        assert_eq!(model.scenes[0].nodes, vec![0, 2]);
+        assert_eq!(model.scene, Some(0));
+
+        let world = model.world_matrices();
+        let close = |u: Vec3, v: Vec3| assert!((u - v).norm() < 1e-5, "{u} != {v}");
+        close(world[1].transform(Vec3(1.0, 0.0, 0.0)), Vec3(1.0, 2.0, 0.0));
+        close(world[2].transform(Vec3(1.0, 0.0, 0.0)), Vec3(1.0, 0.0, 3.0));
+
+        let json = triangle_json(Some("triangle.bin"), r#""nodes": [{ "children": [1] }, { "children": [0] }],"#);
+        assert!(matches!(Model::try_decode(json.as_bytes(), &bin), Err(Error::Hierarchy)));
+    }
+
+    #[test]
+    fn animation()
+    {
+        let mut anim = Vec::new();
+        let s = std::f32::consts::FRAC_1_SQRT_2;
+        let floats = [
+            0.0, 1.0, 2.0, //times
+            0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 2.0, 4.0, 0.0, //translations
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, s, s, 0.0, 0.0, 1.0, 0.0, //rotations
+            0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, //cubic translations: in tangent, value, out tangent
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 1.0, 0.0, 0.5, 0.5, //weights
+            1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, -1.0, 0.0, 0.0, 1.0 //inverse bind matrix
+        ];
+        for float in floats { anim.extend_from_slice(&f32::to_le_bytes(float)); }
+        for _ in 0..3 { anim.extend_from_slice(&[0, 0, 0, 0]); } //joints
+        for _ in 0..3 { anim.extend_from_slice(&[255, 0, 0, 0]); } //weights
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "uri": "triangle.bin", "byteLength": 44 }, { "uri": "anim.bin", "byteLength": 316 }],
+            "bufferViews":
+            [
+                { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+                { "buffer": 0, "byteOffset": 36, "byteLength": 6 },
+                { "buffer": 1, "byteOffset": 0, "byteLength": 12 },
+                { "buffer": 1, "byteOffset": 12, "byteLength": 36 },
+                { "buffer": 1, "byteOffset": 48, "byteLength": 48 },
+                { "buffer": 1, "byteOffset": 96, "byteLength": 108 },
+                { "buffer": 1, "byteOffset": 204, "byteLength": 24 },
+                { "buffer": 1, "byteOffset": 228, "byteLength": 64 },
+                { "buffer": 1, "byteOffset": 292, "byteLength": 12 },
+                { "buffer": 1, "byteOffset": 304, "byteLength": 12 }
+            ],
+            "accessors":
+            [
+                { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] },
+                { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" },
+                { "bufferView": 2, "componentType": 5126, "count": 3, "type": "SCALAR", "min": [0], "max": [2] },
+                { "bufferView": 3, "componentType": 5126, "count": 3, "type": "VEC3" },
+                { "bufferView": 4, "componentType": 5126, "count": 3, "type": "VEC4" },
+                { "bufferView": 5, "componentType": 5126, "count": 9, "type": "VEC3" },
+                { "bufferView": 6, "componentType": 5126, "count": 6, "type": "SCALAR" },
+                { "bufferView": 7, "componentType": 5126, "count": 1, "type": "MAT4" },
+                { "bufferView": 8, "componentType": 5121, "count": 3, "type": "VEC4" },
+                { "bufferView": 9, "componentType": 5121, "normalized": true, "count": 3, "type": "VEC4" }
+            ],
+            "meshes": [{ "name": "triangle", "primitives": [{ "attributes": { "POSITION": 0, "JOINTS_0": 8, "WEIGHTS_0": 9 }, "indices": 1 }] }],
+            "nodes": [{ "name": "joint" }, { "mesh": 0, "skin": 0, "weights": [0.25, 0.75] }],
+            "skins": [{ "joints": [0], "inverseBindMatrices": 7 }],
+            "animations":
+            [{
+                "samplers":
+                [
+                    { "input": 2, "output": 3 },
+                    { "input": 2, "output": 4 },
+                    { "input": 2, "output": 4, "interpolation": "STEP" },
+                    { "input": 2, "output": 5, "interpolation": "CUBICSPLINE" },
+                    { "input": 2, "output": 6 }
+                ],
+                "channels":
+                [
+                    { "sampler": 0, "target": { "node": 0, "path": "translation" } },
+                    { "sampler": 1, "target": { "node": 0, "path": "rotation" } },
+                    { "sampler": 2, "target": { "node": 1, "path": "rotation" } },
+                    { "sampler": 3, "target": { "node": 1, "path": "translation" } },
+                    { "sampler": 4, "target": { "node": 1, "path": "weights" } }
+                ]
+            }]
+        }"#;
+        let triangle = triangle_bin();
+        let model = Model::load(json.as_bytes(), |uri| match uri
+        {
+            "triangle.bin" => Some(triangle.clone()),
+            "anim.bin" => Some(anim.clone()),
+            _ => None
+        });
+        assert_eq!(model.joints, Some(vec![[0; 4]; 3]));
+        assert_eq!(model.weights, Some(vec![Vec4(1.0, 0.0, 0.0, 0.0); 3]));
+        assert_eq!(model.nodes[1].skin, Some(0));
+        assert_eq!(model.nodes[1].weights, vec![0.25, 0.75]);
+        assert_eq!(model.skins[0].joints, vec![0]);
+        assert_eq!(model.animations[0].channels.len(), 5);
+        assert_eq!(model.animations[0].duration(), 2.0);
+
+        let close = |u: Vec3, v: Vec3| assert!((u - v).norm() < 1e-5, "{u} != {v}");
+        let close_weights = |u: &[f32], v: &[f32]| assert!(u.iter().zip(v).all(|(a, b)| (a - b).abs() < 1e-5), "{u:?} != {v:?}");
+
+        let pose = model.sample(0, 0.5);
+        close(pose.translations[0], Vec3(1.0, 0.0, 0.0));
+        close(pose.rotations[0].transform(Vec3(1.0, 0.0, 0.0)), Vec3(s, s, 0.0));
+        close(pose.rotations[1].transform(Vec3(1.0, 0.0, 0.0)), Vec3(1.0, 0.0, 0.0));
+        close(pose.translations[1], Vec3(0.625, 0.0, 0.0));
+        close_weights(&pose.weights[1], &[0.5, 0.5]);
+        let world = pose.world_matrices(&model.nodes);
+        let joints = model.skins[0].joint_matrices(&world);
+        close(joints[0].transform(Vec3(1.0, 0.0, 0.0)), Vec3(1.0, 0.0, 0.0));
+        close(joints[0].transform(Vec3(2.0, 0.0, 0.0)), Vec3(1.0 + s, s, 0.0));
+
+        let pose = model.sample(0, 1.5);
+        close(pose.translations[0], Vec3(2.0, 2.0, 0.0));
+        close(pose.rotations[0].transform(Vec3(1.0, 0.0, 0.0)), Vec3(-s, s, 0.0));
+        close(pose.rotations[1].transform(Vec3(1.0, 0.0, 0.0)), Vec3(0.0, 1.0, 0.0));
+        close_weights(&pose.weights[1], &[0.75, 0.25]);
+
+        let pose = model.sample(0, 5.0);
+        close(pose.translations[0], Vec3(2.0, 4.0, 0.0));
+        close(pose.translations[1], Vec3(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn primitives()
+    {
+        let mut bin = triangle_bin()[..36].to_vec(); //positions
+        bin.extend_from_slice(&[0, 1, 2, 0]); //u8 indices
+        for int in [0u16, 0, 65535, 0, 0, 65535] { bin.extend_from_slice(&int.to_le_bytes()); } //uv set 0
+        bin.extend_from_slice(&[255, 255, 0, 0, 255, 0, 0, 0]); //uv set 1
+        bin.extend_from_slice(&[255, 0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0]); //rgb colors
+        bin.extend_from_slice(&[1, 0, 0, 0]); //sparse indices
+        for float in [5.0f32, 5.0, 5.0] { bin.extend_from_slice(&float.to_le_bytes()); } //sparse values
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": 88 }],
+            "bufferViews":
+            [
+                { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+                { "buffer": 0, "byteOffset": 36, "byteLength": 3 },
+                { "buffer": 0, "byteOffset": 40, "byteLength": 12 },
+                { "buffer": 0, "byteOffset": 52, "byteLength": 6 },
+                { "buffer": 0, "byteOffset": 60, "byteLength": 9 },
+                { "buffer": 0, "byteOffset": 72, "byteLength": 1 },
+                { "buffer": 0, "byteOffset": 76, "byteLength": 12 }
+            ],
+            "accessors":
+            [
+                { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] },
+                { "bufferView": 1, "componentType": 5121, "count": 3, "type": "SCALAR" },
+                { "bufferView": 2, "componentType": 5123, "normalized": true, "count": 3, "type": "VEC2" },
+                { "bufferView": 3, "componentType": 5121, "normalized": true, "count": 3, "type": "VEC2" },
+                { "bufferView": 4, "componentType": 5121, "normalized": true, "count": 3, "type": "VEC3" },
+                {
+                    "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [5, 5, 5],
+                    "sparse": { "count": 1, "indices": { "bufferView": 5, "componentType": 5121 }, "values": { "bufferView": 6 } }
+                },
+                {
+                    "componentType": 5126, "count": 3, "type": "VEC2",
+                    "sparse": { "count": 1, "indices": { "bufferView": 5, "componentType": 5121 }, "values": { "bufferView": 6 } }
+                }
+            ],
+            "meshes":
+            [{
+                "name": "multi",
+                "primitives":
+                [
+                    { "attributes": { "POSITION": 0, "TEXCOORD_0": 2, "TEXCOORD_1": 3, "COLOR_0": 4 }, "indices": 1 },
+                    { "attributes": { "POSITION": 5, "TEXCOORD_0": 6, "TEXCOORD_1": 3, "COLOR_0": 4 }, "indices": 1 }
+                ]
+            }],
+            "nodes": [{ "mesh": 0 }]
+        }"#;
+        let model = Model::load(&glb(json, &bin), |_| None);
+        assert_eq!(model.meshes.len(), 2);
+        assert_eq!(model.meshes[1].name, "multi");
+        assert_eq!(model.meshes[0].vertices, 0..3);
+        assert_eq!(model.meshes[1].vertices, 3..6);
+        assert_eq!(model.meshes[1].indices, 3..6);
+        assert_eq!(model.nodes[0].meshes, 0..2);
+        assert_eq!(model.indices, vec![0, 1, 2, 0, 1, 2]);
+        let round_trip = Model::load(&model.encode_glb().unwrap(), |_| None);
+        assert_eq!((&round_trip.meshes, &round_trip.nodes[0].meshes), (&model.meshes, &(0..2)));
+        assert_eq!((&round_trip.tex_coords_1, &round_trip.colors), (&model.tex_coords_1, &model.colors));
+        assert_eq!(model.positions[3..], [Vec3(0.0, 0.0, 0.0), Vec3(5.0, 5.0, 5.0), Vec3(0.0, 1.0, 0.0)]);
+        assert_eq!(model.tex_coords.unwrap(), vec![Vec2(0.0, 0.0), Vec2(1.0, 0.0), Vec2(0.0, 1.0), Vec2(0.0, 0.0), Vec2(5.0, 5.0), Vec2(0.0, 0.0)]);
+        assert_eq!(model.tex_coords_1.unwrap()[3..], [Vec2(1.0, 1.0), Vec2(0.0, 0.0), Vec2(1.0, 0.0)]);
+        assert_eq!(model.colors.unwrap()[..3], [Vec4(1.0, 0.0, 0.0, 1.0), Vec4(0.0, 1.0, 0.0, 1.0), Vec4(0.0, 0.0, 1.0, 1.0)]);
+    }
+
+    #[test]
+    fn materials()
+    {
+        let mut bin = triangle_bin();
+        bin.extend_from_slice(b"\x89PNG"); //embedded image
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": 48 }],
+            "bufferViews":
+            [
+                { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+                { "buffer": 0, "byteOffset": 36, "byteLength": 6 },
+                { "buffer": 0, "byteOffset": 44, "byteLength": 4 }
+            ],
+            "accessors":
+            [
+                { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] },
+                { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }
+            ],
+            "images":
+            [
+                { "bufferView": 2, "mimeType": "image/png" },
+                { "uri": "data:image/jpeg;base64,/9j/" },
+                { "uri": "normal.png", "name": "normal" }
+            ],
+            "samplers": [{ "magFilter": 9728, "minFilter": 9987, "wrapS": 33071, "wrapT": 33648 }],
+            "textures": [{ "source": 0, "sampler": 0 }, { "source": 1 }, { "source": 2 }],
+            "materials":
+            [
+                {
+                    "name": "glass",
+                    "pbrMetallicRoughness":
+                    {
+                        "baseColorFactor": [1, 0.5, 0.25, 0.5], "metallicFactor": 0.25, "roughnessFactor": 0.75,
+                        "baseColorTexture": { "index": 0, "extensions": { "KHR_texture_transform": { "offset": [0.5, 0], "rotation": 1.5, "scale": [2, 2], "texCoord": 1 } } },
+                        "metallicRoughnessTexture": { "index": 1, "texCoord": 1 }
+                    },
+                    "normalTexture": { "index": 2, "scale": 0.5, "extensions": { "KHR_texture_transform": { "scale": [4, 4] } } },
+                    "occlusionTexture": { "index": 1, "strength": 0.75 },
+                    "emissiveFactor": [1, 1, 0],
+                    "alphaMode": "MASK",
+                    "alphaCutoff": 0.25,
+                    "doubleSided": true,
+                    "extensions":
+                    {
+                        "KHR_materials_emissive_strength": { "emissiveStrength": 4 },
+                        "KHR_materials_ior": { "ior": 1.25 },
+                        "KHR_materials_transmission": { "transmissionFactor": 0.5 },
+                        "KHR_materials_clearcoat": { "clearcoatFactor": 1, "clearcoatNormalTexture": { "index": 2 } },
+                        "KHR_materials_sheen": { "sheenColorFactor": [1, 0, 0], "sheenRoughnessFactor": 0.5 }
+                    }
+                },
+                { "alphaMode": "BLEND", "extensions": { "KHR_materials_unlit": {} } }
+            ],
+            "meshes":
+            [{
+                "name": "triangle",
+                "primitives":
+                [
+                    { "attributes": { "POSITION": 0 }, "indices": 1, "material": 0 },
+                    { "attributes": { "POSITION": 0 }, "indices": 1, "material": 1 },
+                    { "attributes": { "POSITION": 0 }, "indices": 1 }
+                ]
+            }]
+        }"#;
+        let model = Model::load(&glb(json, &bin), |_| None);
+        assert_eq!(model.meshes.iter().map(|mesh| mesh.material).collect::<Vec<_>>(), vec![Some(0), Some(1), None]);
+
+        assert!(matches!(&model.images[0].source, ImageSource::Bytes(bytes) if bytes == b"\x89PNG"));
+        assert_eq!(model.images[0].mime_type.as_deref(), Some("image/png"));
+        assert!(matches!(&model.images[1].source, ImageSource::Bytes(bytes) if bytes == &[0xFF, 0xD8, 0xFF]));
+        assert_eq!(model.images[1].mime_type.as_deref(), Some("image/jpeg"));
+        assert!(matches!(&model.images[2].source, ImageSource::Uri(uri) if uri == "normal.png"));
+        assert_eq!(model.images[2].name.as_deref(), Some("normal"));
+
+        assert_eq!(model.textures[0].image, 0);
+        assert_eq!(model.textures[0].sampler, Sampler { mag_filter: Some(Filter::Nearest), min_filter: Some(Filter::Linear), mipmap_filter: Some(Filter::Linear), wrap_s: Wrap::ClampToEdge, wrap_t: Wrap::MirroredRepeat });
+        assert_eq!(model.textures[1].sampler, Sampler { mag_filter: None, min_filter: None, mipmap_filter: None, wrap_s: Wrap::Repeat, wrap_t: Wrap::Repeat });
+
+        let glass = &model.materials[0];
+        assert_eq!(glass.name.as_deref(), Some("glass"));
+        assert_eq!(glass.base_color, Vec4(1.0, 0.5, 0.25, 0.5));
+        assert_eq!(glass.base_color_texture, Some(TextureInfo
+        {
+            texture: 0,
+            tex_coord: 0,
+            transform: Some(TextureTransform { offset: Vec2(0.5, 0.0), rotation: 1.5, scale: Vec2(2.0, 2.0), tex_coord: Some(1) })
+        }));
+        assert_eq!((glass.metallic, glass.roughness), (0.25, 0.75));
+        assert_eq!(glass.metallic_roughness_texture, Some(TextureInfo { texture: 1, tex_coord: 1, transform: None }));
+        assert_eq!(glass.normal_texture.unwrap().transform.unwrap().scale, Vec2(4.0, 4.0));
+        assert_eq!((glass.normal_scale, glass.occlusion_strength), (0.5, 0.75));
+        assert_eq!(glass.emissive, Vec3(1.0, 1.0, 0.0));
+        assert_eq!(glass.emissive_strength, 4.0);
+        assert_eq!(glass.alpha_mode, AlphaMode::Mask(0.25));
+        assert!(glass.double_sided && !glass.unlit);
+        assert_eq!(glass.ior, 1.25);
+        assert_eq!(glass.transmission, Some(Transmission { factor: 0.5, texture: None }));
+        assert_eq!(glass.clearcoat.unwrap().factor, 1.0);
+        assert_eq!(glass.clearcoat.unwrap().normal_texture.unwrap().texture, 2);
+        assert_eq!(glass.sheen, Some(Sheen { color: Vec3(1.0, 0.0, 0.0), color_texture: None, roughness: 0.5, roughness_texture: None }));
+        assert!(glass.volume.is_none() && glass.specular.is_none());
+
+        assert_eq!(model.materials[1].alpha_mode, AlphaMode::Blend);
+        assert!(model.materials[1].unlit);
+        assert_eq!(Material { alpha_mode: AlphaMode::Blend, unlit: true, ..Material::default() }, model.materials[1]);
+    }
+
+    #[test]
+    fn round_trip()
+    {
+        let bin = triangle_bin();
+        let mut model = Model::decode(triangle_json(Some("triangle.bin"), NODES).as_bytes(), &bin);
+        model.normals = Some(vec![Vec3::e_z(); 3]);
+        model.tex_coords = Some(model.positions.iter().map(|p| Vec2(1.0 - p.0, p.1)).collect());
+        assert!(model.generate_tangents());
+        model.materials.push(Material { name: Some("red \"quoted\"".to_owned()), base_color: Vec4(1.0, 0.0, 0.0, 1.0), alpha_mode: AlphaMode::Mask(0.3), double_sided: true, ..Material::default() });
+        model.meshes[0].material = Some(0);
+
+        let (gltf, gltf_bin) = model.encode("model.bin").unwrap();
+        let decoded = [Model::load(&model.encode_glb().unwrap(), |_| None), Model::load(&gltf, |uri| (uri == "model.bin").then(|| gltf_bin.clone()))];
+        for decoded in decoded
+        {
+            assert_eq!(decoded.positions, model.positions);
+            assert_eq!(decoded.normals, model.normals);
+            assert_eq!(decoded.tangents, model.tangents);
+            assert_eq!(decoded.tangents.as_ref().unwrap()[0].3, -1.0);
+            assert_eq!(decoded.tex_coords, model.tex_coords);
+            assert_eq!(decoded.indices, model.indices);
+            assert_eq!(decoded.meshes, model.meshes);
+            assert_eq!(decoded.materials, model.materials);
+            assert_eq!(decoded.scene, model.scene);
+            assert_eq!(decoded.scenes[0].nodes, model.scenes[0].nodes);
+            for (a, b) in decoded.nodes.iter().zip(&model.nodes)
+            {
+                assert_eq!((&a.name, &a.children, a.parent, &a.meshes), (&b.name, &b.children, b.parent, &b.meshes));
+            }
+            for (a, b) in decoded.world_matrices().iter().zip(model.world_matrices())
+            {
+                let p = Vec3(0.3, -0.7, 1.1);
+                assert!((a.transform(p) - b.transform(p)).norm() < 1e-5);
+            }
         }
     }
+
+    #[test]
+    fn encode_errors()
+    {
+        //nothing to write, no empty buffer or buffer view
+        let (gltf, bin) = Model::default().encode("model.bin").unwrap();
+        assert!(bin.is_empty() && !String::from_utf8(gltf.clone()).unwrap().contains("buffer"));
+        let empty = Model::load(&gltf, |_| None);
+        assert!(empty.positions.is_empty() && empty.meshes.is_empty() && empty.nodes.is_empty());
+        assert!(Model::load(&Model::default().encode_glb().unwrap(), |_| None).meshes.is_empty());
+
+        let triangle = Model::decode(triangle_json(Some("triangle.bin"), "").as_bytes(), &triangle_bin());
+        let mut model = triangle.clone();
+        model.meshes[0].vertices = 0..0;
+        assert!(matches!(model.encode_glb(), Err(Error::EmptyMesh(name)) if name == "triangle"));
+        model.meshes[0] = Mesh { indices: 3..3, ..triangle.meshes[0].clone() };
+        assert!(matches!(model.encode_glb(), Err(Error::EmptyMesh(_))));
+
+        let mut model = triangle.clone();
+        model.positions[1].0 = f32::NAN;
+        assert!(matches!(model.encode_glb(), Err(Error::NonFinite("positions"))));
+
+        //ranges and indices must stay inside the model and the mesh, attributes must match the positions
+        let mut model = triangle.clone();
+        model.meshes[0].vertices = 1..4;
+        assert!(matches!(model.encode_glb(), Err(Error::MeshVertices(name)) if name == "triangle"));
+        model.meshes[0] = Mesh { indices: 0..4, ..triangle.meshes[0].clone() };
+        assert!(matches!(model.encode_glb(), Err(Error::MeshIndices(_))));
+        model.meshes[0] = Mesh { vertices: 0..2, ..triangle.meshes[0].clone() };
+        assert!(matches!(model.encode_glb(), Err(Error::MeshIndices(_))));
+        model.meshes[0] = Mesh { material: Some(0), ..triangle.meshes[0].clone() };
+        assert!(matches!(model.encode_glb(), Err(Error::MeshMaterial(_))));
+        let mut model = triangle.clone();
+        model.normals = Some(vec![Vec3::e_z(); 2]);
+        assert!(matches!(model.encode_glb(), Err(Error::AttributeLength("normals"))));
+        model.normals = None;
+        model.colors = Some(Vec::new());
+        assert!(matches!(model.encode_glb(), Err(Error::AttributeLength("colors"))));
+
+        //two primitives of one name, nodes can take both or one each but not overlap
+        let mut model = Model::decode(triangle_json(Some("triangle.bin"), NODES).as_bytes(), &triangle_bin());
+        model.meshes.push(model.meshes[0].clone());
+        model.nodes[1].meshes = 0..2;
+        let decoded = Model::load(&model.encode_glb().unwrap(), |_| None);
+        assert_eq!((decoded.meshes.len(), decoded.nodes[1].meshes.clone()), (2, 0..2));
+        model.nodes[1].meshes = 0..1;
+        model.nodes[2].meshes = 1..2;
+        let decoded = Model::load(&model.encode_glb().unwrap(), |_| None);
+        assert_eq!((decoded.nodes[1].meshes.clone(), decoded.nodes[2].meshes.clone()), (0..1, 1..2));
+        model.nodes[2].meshes = 0..2;
+        assert!(matches!(model.encode_glb(), Err(Error::NodeMeshes(1))));
+        model.nodes[2].meshes = 1..3;
+        assert!(matches!(model.encode_glb(), Err(Error::NodeMeshes(2))));
+    }
+
+    #[test]
+    fn generate_tangents()
+    {
+        //a quad with a mirrored uv seam along its diagonal, followed by the triangle
+        let mut model = Model::decode(triangle_json(Some("triangle.bin"), "").as_bytes(), &triangle_bin());
+        model.positions.splice(0..0, [Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), Vec3(1.0, 1.0, 0.0), Vec3(0.0, 1.0, 0.0)]);
+        model.indices = [0, 1, 2, 0, 2, 3].into_iter().chain(model.indices).collect();
+        model.meshes[0].vertices = 4..7;
+        model.meshes[0].indices = 6..9;
+        model.meshes.insert(0, Mesh { name: "quad".to_owned(), vertices: 0..4, indices: 0..6, material: None });
+        model.normals = Some(vec![Vec3::e_z(); 7]);
+        model.tex_coords = Some(vec![Vec2(0.0, 0.0), Vec2(1.0, 0.0), Vec2(1.0, 1.0), Vec2(2.0, 1.0), Vec2(0.0, 0.0), Vec2(1.0, 0.0), Vec2(0.0, 1.0)]);
+        let triangle = model.positions[4..].to_vec();
+        assert!(model.generate_tangents());
+
+        assert_eq!((model.meshes[0].vertices.clone(), model.meshes[1].vertices.clone()), (0..6, 6..9));
+        assert_eq!(model.indices, [0, 1, 2, 4, 5, 3, 0, 1, 2]);
+        assert_eq!(&model.positions[6..], &triangle);
+        assert_eq!((model.positions[4], model.positions[5]), (model.positions[0], model.positions[2]));
+        let (tangents, tex_coords) = (model.tangents.unwrap(), model.tex_coords.unwrap());
+        assert_eq!(tex_coords[4..6], [Vec2(0.0, 0.0), Vec2(1.0, 1.0)]);
+        assert_eq!((tangents[0].3, tangents[4].3, tangents[5].3, tangents[6].3), (1.0, -1.0, -1.0, 1.0));
+        assert_eq!(model.normals.unwrap().len(), 9);
+    }
 }
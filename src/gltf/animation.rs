@@ -0,0 +1,233 @@
+use super::*;
+use crate::math::{Rotor, Slerp, Mat4};
+use std::ops::{Add, Mul};
+
+#[derive(Debug, Clone)]
+pub struct Skin
+{
+    pub name: Option<String>,
+    pub joints: Vec<usize>, //indices into Model::nodes
+    pub inverse_bind_matrices: Vec<Mat4>,
+    pub skeleton: Option<usize> //common root of the joints
+}
+
+impl Skin
+{
+    //world matrices of the joints times their inverse bind matrices
+    //for vertices in the space of the skinned node multiply from the left with the inverse world matrix of that node
+    pub fn joint_matrices(&self, world: &[Mat4]) -> Vec<Mat4>
+    {
+        self.joints.iter().zip(&self.inverse_bind_matrices).map(|(&joint, &inverse_bind)| world[joint] * inverse_bind).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation
+{
+    Linear,
+    Step,
+    CubicSpline //values are stored as (in tangent, value, out tangent) per keyframe
+}
+
+#[derive(Debug, Clone)]
+pub enum Values
+{
+    Translations(Vec<Vec3>),
+    Rotations(Vec<Rotor>),
+    Scales(Vec<Vec3>),
+    Weights(Vec<f32>) //all morph target weights of a keyframe are consecutive
+}
+
+#[derive(Debug, Clone)]
+pub struct Channel
+{
+    pub node: usize, //index into Model::nodes
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>, //in seconds, strictly increasing
+    pub values: Values
+}
+
+#[derive(Debug, Clone)]
+pub struct Animation
+{
+    pub name: Option<String>,
+    pub channels: Vec<Channel>
+}
+
+//local transforms and morph weights of all nodes, indexed like Model::nodes
+#[derive(Debug, Clone)]
+pub struct Pose
+{
+    pub translations: Vec<Vec3>,
+    pub rotations: Vec<Rotor>,
+    pub scales: Vec<Vec3>,
+    pub weights: Vec<Vec<f32>>
+}
+
+impl Pose
+{
+    pub fn rest(nodes: &[Node]) -> Self
+    {
+        Self
+        {
+            translations: nodes.iter().map(|node| node.translation).collect(),
+            rotations: nodes.iter().map(|node| node.rotation).collect(),
+            scales: nodes.iter().map(|node| node.scale).collect(),
+            weights: nodes.iter().map(|node| node.weights.clone()).collect()
+        }
+    }
+
+    #[inline]
+    pub fn local_matrix(&self, node: usize) -> Mat4
+    {
+        Mat4::translation(self.translations[node]) * self.rotations[node].to_mat4() * Mat4::scale(self.scales[node])
+    }
+
+    pub fn world_matrices(&self, nodes: &[Node]) -> Vec<Mat4>
+    {
+        scene::world_matrices(nodes, |i| self.local_matrix(i))
+    }
+}
+
+impl Animation
+{
+    pub fn duration(&self) -> f32
+    {
+        self.channels.iter().map(|channel| *channel.times.last().unwrap()).fold(0.0, f32::max)
+    }
+
+    //overwrites the animated properties of the pose, t is clamped to the keyframes of each channel
+    pub fn sample(&self, t: f32, pose: &mut Pose)
+    {
+        for channel in &self.channels
+        {
+            let (i, j, s, dt) = keyframe(&channel.times, t);
+            let node = channel.node;
+            match &channel.values
+            {
+                Values::Translations(values) => pose.translations[node] = interpolate(channel.interpolation, |k| values[k], i, j, s, dt),
+                Values::Scales(values) => pose.scales[node] = interpolate(channel.interpolation, |k| values[k], i, j, s, dt),
+                Values::Rotations(values) => pose.rotations[node] = match channel.interpolation
+                {
+                    Interpolation::Linear => Slerp::new(values[i], values[j]).get(s),
+                    Interpolation::Step => values[i],
+                    Interpolation::CubicSpline =>
+                    {
+                        let to_vec = |k: usize| { let (s, [yz, zx, xy]) = values[k].to_components(); Vec4(yz, zx, xy, s) };
+                        let Vec4(yz, zx, xy, s) = cubic(to_vec, i, j, s, dt);
+                        Rotor::from_quaternion(-yz, -zx, -xy, s).fix()
+                    }
+                },
+                Values::Weights(values) =>
+                {
+                    let keys = channel.times.len() * if channel.interpolation == Interpolation::CubicSpline { 3 } else { 1 };
+                    let n = values.len() / keys;
+                    pose.weights[node] = (0..n).map(|m| interpolate(channel.interpolation, |k| values[k * n + m], i, j, s, dt)).collect();
+                }
+            }
+        }
+    }
+}
+
+impl Model
+{
+    pub fn sample(&self, animation: usize, t: f32) -> Pose
+    {
+        let mut pose = Pose::rest(&self.nodes);
+        self.animations[animation].sample(t, &mut pose);
+        pose
+    }
+}
+
+//the keyframes around t, the position between them and their distance
+fn keyframe(times: &[f32], t: f32) -> (usize, usize, f32, f32)
+{
+    let next = times.partition_point(|&time| time <= t);
+    if next == 0 { return (0, 0, 0.0, 0.0); }
+    if next == times.len() { return (next - 1, next - 1, 0.0, 0.0); }
+    let (i, j) = (next - 1, next);
+    let dt = times[j] - times[i];
+    (i, j, (t - times[i]) / dt, dt)
+}
+
+fn interpolate<T: Copy + Add<Output = T> + Mul<f32, Output = T>>(interpolation: Interpolation, get: impl Fn(usize) -> T, i: usize, j: usize, s: f32, dt: f32) -> T
+{
+    match interpolation
+    {
+        Interpolation::Linear => get(i) * (1.0 - s) + get(j) * s,
+        Interpolation::Step => get(i),
+        Interpolation::CubicSpline => cubic(get, i, j, s, dt)
+    }
+}
+
+//hermite spline, the tangents are scaled by the keyframe distance
+fn cubic<T: Copy + Add<Output = T> + Mul<f32, Output = T>>(get: impl Fn(usize) -> T, i: usize, j: usize, s: f32, dt: f32) -> T
+{
+    let (s2, s3) = (s * s, s * s * s);
+    get(3 * i + 1) * (2.0 * s3 - 3.0 * s2 + 1.0)
+     + get(3 * i + 2) * ((s3 - 2.0 * s2 + s) * dt)
+     + get(3 * j + 1) * (3.0 * s2 - 2.0 * s3)
+     + get(3 * j) * ((s3 - s2) * dt)
+}
+
+pub(super) fn decode_skin(skin: &::gltf::Skin, buffers: &Buffers) -> Result<Skin, Error>
+{
+    let joints: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+    let inverse_bind_matrices = match skin.inverse_bind_matrices()
+    {
+        Some(accessor) =>
+        {
+            let floats = buffers.floats(&accessor)?;
+            if floats.len() != joints.len() * 16 { return Err(Error::InverseBindMatrices); }
+            floats.into_iter().array_chunks::<4>().map(Vec4::from).array_chunks().map(|[c0, c1, c2, c3]| Mat4(c0, c1, c2, c3)).collect()
+        },
+        None => vec![Mat4::identity(); joints.len()]
+    };
+    Ok(Skin
+    {
+        name: skin.name().map(str::to_owned),
+        joints,
+        inverse_bind_matrices,
+        skeleton: skin.skeleton().map(|node| node.index())
+    })
+}
+
+pub(super) fn decode_animation(animation: &::gltf::Animation, buffers: &Buffers) -> Result<Animation, Error>
+{
+    let channels = animation.channels().map(|channel|
+    {
+        let sampler = channel.sampler();
+        let interpolation = match sampler.interpolation()
+        {
+            ::gltf::animation::Interpolation::Linear => Interpolation::Linear,
+            ::gltf::animation::Interpolation::Step => Interpolation::Step,
+            ::gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline
+        };
+        let times = buffers.floats(&sampler.input())?;
+        let floats = buffers.floats(&sampler.output())?;
+
+        let keys = times.len() * if interpolation == Interpolation::CubicSpline { 3 } else { 1 };
+        let values = match channel.target().property()
+        {
+            ::gltf::animation::Property::Translation => Values::Translations(floats.into_iter().array_chunks().map(|[x, y, z]| Vec3(x, y, z)).collect()),
+            ::gltf::animation::Property::Scale => Values::Scales(floats.into_iter().array_chunks().map(|[x, y, z]| Vec3(x, y, z)).collect()),
+            ::gltf::animation::Property::Rotation => Values::Rotations(floats.into_iter().array_chunks().map(|[x, y, z, w]| Rotor::from_quaternion(x, y, z, w)).collect()),
+            ::gltf::animation::Property::MorphTargetWeights => Values::Weights(floats)
+        };
+        let valid = match &values
+        {
+            Values::Translations(values) | Values::Scales(values) => values.len() == keys,
+            Values::Rotations(values) => values.len() == keys,
+            Values::Weights(values) => values.len() % keys == 0
+        };
+        if times.is_empty() || !valid { return Err(Error::Keyframes); }
+
+        Ok(Channel { node: channel.target().node().index(), interpolation, times, values })
+    }).collect::<Result<_, _>>()?;
+
+    Ok(Animation
+    {
+        name: animation.name().map(str::to_owned),
+        channels
+    })
+}
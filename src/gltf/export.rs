@@ -0,0 +1,258 @@
+use super::*;
+use gltf::json::{self, validation::Checked::Valid, accessor::{ComponentType, GenericComponentType, Type}, buffer::Target};
+use std::{borrow::Cow, collections::BTreeMap};
+
+//writes positions, normals, tangents, uvs, colors, indices, nodes, scenes and material constants
+//consecutive meshes with the same name become the primitives of one glTF mesh, as Model::decode splits them
+//textures, skins and animations are not written
+
+impl Model
+{
+    //returns the .gltf and the .bin, bin_uri is the path of the .bin relative to the .gltf
+    pub fn encode(&self, bin_uri: &str) -> Result<(Vec<u8>, Vec<u8>), Error>
+    {
+        let (root, bin) = self.encode_root(Some(bin_uri))?;
+        Ok((json::serialize::to_vec(&root).map_err(Error::Json)?, bin))
+    }
+
+    pub fn encode_glb(&self) -> Result<Vec<u8>, Error>
+    {
+        let (root, bin) = self.encode_root(None)?;
+        let json = json::serialize::to_vec(&root).map_err(Error::Json)?;
+        let glb = Glb
+        {
+            header: binary::Header { magic: *b"glTF", version: 2, length: 0 }, //the length is computed when writing
+            json: Cow::Owned(json),
+            bin: (!bin.is_empty()).then_some(Cow::Owned(bin))
+        };
+        glb.to_vec().map_err(Error::Gltf)
+    }
+
+    fn encode_root(&self, bin_uri: Option<&str>) -> Result<(json::Root, Vec<u8>), Error>
+    {
+        let mut root = json::Root { asset: json::Asset { generator: Some("gru-misc".to_owned()), ..Default::default() }, ..Default::default() };
+        let mut bin = Vec::new();
+
+        //one buffer view per attribute, one accessor per attribute and primitive
+        let view = |root: &mut json::Root, bin: &mut Vec<u8>, bytes: &[u8], target: Target| -> json::Index<json::buffer::View>
+        {
+            let offset = bin.len();
+            bin.extend_from_slice(bytes);
+            root.push(json::buffer::View
+            {
+                buffer: json::Index::new(0),
+                byte_length: bytes.len().into(),
+                byte_offset: Some(offset.into()),
+                byte_stride: None,
+                name: None,
+                target: Some(Valid(target)),
+                extensions: None,
+                extras: Default::default()
+            })
+        };
+        let vec2s = |vectors: &[Vec2]| -> Vec<f32> { vectors.iter().flat_map(|v| [v.0, v.1]).collect() };
+        let vec3s = |vectors: &[Vec3]| -> Vec<f32> { vectors.iter().flat_map(|v| [v.0, v.1, v.2]).collect() };
+        let vec4s = |vectors: &[Vec4]| -> Vec<f32> { vectors.iter().flat_map(|v| [v.0, v.1, v.2, v.3]).collect() };
+        let attributes =
+        [
+            ("positions", Semantic::Positions, Type::Vec3, 3, Some(vec3s(&self.positions))),
+            ("normals", Semantic::Normals, Type::Vec3, 3, self.normals.as_deref().map(vec3s)),
+            ("tangents", Semantic::Tangents, Type::Vec4, 4, self.tangents.as_deref().map(vec4s)),
+            ("uvs", Semantic::TexCoords(0), Type::Vec2, 2, self.tex_coords.as_deref().map(vec2s)),
+            ("second uvs", Semantic::TexCoords(1), Type::Vec2, 2, self.tex_coords_1.as_deref().map(vec2s)),
+            ("colors", Semantic::Colors(0), Type::Vec4, 4, self.colors.as_deref().map(vec4s))
+        ];
+        let mut views = Vec::new();
+        for (name, semantic, kind, components, floats) in attributes
+        {
+            let Some(floats) = floats else { continue };
+            if floats.len() != self.positions.len() * components { return Err(Error::AttributeLength(name)); }
+            if floats.is_empty() { continue; }
+            finite(name, &floats)?;
+            let bytes: Vec<u8> = floats.iter().flat_map(|float| float.to_le_bytes()).collect();
+            views.push((semantic, kind, components, view(&mut root, &mut bin, &bytes, Target::ArrayBuffer)));
+        }
+        let index_view = (!self.indices.is_empty()).then(||
+        {
+            let bytes: Vec<u8> = self.indices.iter().flat_map(|index| index.to_le_bytes()).collect();
+            view(&mut root, &mut bin, &bytes, Target::ElementArrayBuffer)
+        });
+
+        let accessor = |view, offset: usize, count: usize, component_type, type_, bounds: Option<(Vec3, Vec3)>| json::Accessor
+        {
+            buffer_view: Some(view),
+            byte_offset: Some(offset.into()),
+            count: count.into(),
+            component_type: Valid(GenericComponentType(component_type)),
+            extensions: None,
+            extras: Default::default(),
+            type_: Valid(type_),
+            min: bounds.map(|(min, _)| json::Value::from(vec![min.0, min.1, min.2])),
+            max: bounds.map(|(_, max)| json::Value::from(vec![max.0, max.1, max.2])),
+            name: None,
+            normalized: false,
+            sparse: None
+        };
+        let primitive = |root: &mut json::Root, mesh: &Mesh| -> Result<json::mesh::Primitive, Error>
+        {
+            let (vertices, indices) = (&mesh.vertices, &mesh.indices);
+            //glTF accessors need at least one element
+            if vertices.is_empty() || indices.is_empty() { return Err(Error::EmptyMesh(mesh.name.clone())); }
+            if vertices.end > self.positions.len() { return Err(Error::MeshVertices(mesh.name.clone())); }
+            if indices.end > self.indices.len() || self.indices[indices.clone()].iter().any(|&index| index as usize >= vertices.len())
+            {
+                return Err(Error::MeshIndices(mesh.name.clone()));
+            }
+            if mesh.material.is_some_and(|material| material >= self.materials.len()) { return Err(Error::MeshMaterial(mesh.name.clone())); }
+            let index_view = index_view.ok_or_else(|| Error::MeshIndices(mesh.name.clone()))?;
+            let mut attributes = BTreeMap::new();
+            for &(ref semantic, kind, components, view) in &views
+            {
+                let bounds = (*semantic == Semantic::Positions).then(||
+                {
+                    let positions = &self.positions[vertices.clone()];
+                    let min = positions.iter().fold(positions[0], |min, p| min.component_min(*p));
+                    let max = positions.iter().fold(positions[0], |max, p| max.component_max(*p));
+                    (min, max)
+                });
+                let index = root.push(accessor(view, vertices.start * components * 4, vertices.len(), ComponentType::F32, kind, bounds));
+                attributes.insert(Valid(semantic.clone()), index);
+            }
+            let indices = root.push(accessor(index_view, indices.start * 4, indices.len(), ComponentType::U32, Type::Scalar, None));
+            Ok(json::mesh::Primitive
+            {
+                attributes,
+                extensions: None,
+                extras: Default::default(),
+                indices: Some(indices),
+                material: mesh.material.map(|material| json::Index::new(material as u32)),
+                mode: Valid(json::mesh::Mode::Triangles),
+                targets: None
+            })
+        };
+
+        if let Some(i) = self.nodes.iter().position(|node| node.meshes.end > self.meshes.len()) { return Err(Error::NodeMeshes(i)); }
+        //glTF meshes: runs of equal names, split where node ranges start or end, merged inside node ranges
+        let mut boundaries = vec![false; self.meshes.len() + 1];
+        boundaries[0] = true;
+        boundaries[self.meshes.len()] = true;
+        for (boundary, pair) in boundaries[1..].iter_mut().zip(self.meshes.windows(2)) { *boundary = pair[0].name != pair[1].name; }
+        let ranges = self.nodes.iter().map(|node| node.meshes.clone()).filter(|range| !range.is_empty());
+        for range in ranges.clone() { boundaries[range.start] = true; boundaries[range.end] = true; }
+        for range in ranges { for boundary in &mut boundaries[(range.start + 1)..range.end] { *boundary = false; } }
+        let starts: Vec<usize> = (0..self.meshes.len()).filter(|&i| boundaries[i]).collect();
+        for &start in &starts
+        {
+            let end = (start + 1..=self.meshes.len()).find(|&i| boundaries[i]).unwrap();
+            let primitives = self.meshes[start..end].iter().map(|mesh| primitive(&mut root, mesh)).collect::<Result<_, _>>()?;
+            root.push(json::Mesh { extensions: None, extras: Default::default(), name: Some(self.meshes[start].name.clone()), primitives, weights: None });
+        }
+
+        //node ranges that partially overlap split each other above, then neither is one glTF mesh
+        let mesh_index = |i: usize, range: &Range<usize>| -> Result<Option<json::Index<json::Mesh>>, Error>
+        {
+            if range.is_empty() { return Ok(None); }
+            let mesh = starts.binary_search(&range.start).map_err(|_| Error::NodeMeshes(i))?;
+            let end = starts.get(mesh + 1).copied().unwrap_or(self.meshes.len());
+            if end != range.end { return Err(Error::NodeMeshes(i)); }
+            Ok(Some(json::Index::new(mesh as u32)))
+        };
+        let node = |mesh: Option<json::Index<json::Mesh>>| json::Node
+        {
+            camera: None,
+            children: None,
+            extensions: None,
+            extras: Default::default(),
+            matrix: None,
+            mesh,
+            name: None,
+            rotation: None,
+            scale: None,
+            translation: None,
+            skin: None,
+            weights: None
+        };
+        let scene = |name: Option<String>, nodes: Vec<json::Index<json::Node>>| json::Scene { extensions: None, extras: Default::default(), name, nodes };
+        if self.nodes.is_empty()
+        {
+            //one node per mesh in a single scene
+            if !starts.is_empty()
+            {
+                let nodes = (0..starts.len() as u32).map(|i| root.push(node(Some(json::Index::new(i))))).collect();
+                root.scene = Some(root.push(scene(None, nodes)));
+            }
+        }
+        else
+        {
+            for (i, model_node) in self.nodes.iter().enumerate()
+            {
+                let (s, [yz, zx, xy]) = model_node.rotation.to_components();
+                let Vec3(tx, ty, tz) = model_node.translation;
+                let Vec3(sx, sy, sz) = model_node.scale;
+                finite("node transforms", &[s, yz, zx, xy, tx, ty, tz, sx, sy, sz])?;
+                root.push(json::Node
+                {
+                    name: model_node.name.clone(),
+                    translation: Some([tx, ty, tz]),
+                    rotation: Some(json::scene::UnitQuaternion([-yz, -zx, -xy, s])),
+                    scale: Some([sx, sy, sz]),
+                    children: (!model_node.children.is_empty()).then(|| model_node.children.iter().map(|&child| json::Index::new(child as u32)).collect()),
+                    ..node(mesh_index(i, &model_node.meshes)?)
+                });
+            }
+            for model_scene in &self.scenes
+            {
+                root.push(scene(model_scene.name.clone(), model_scene.nodes.iter().map(|&node| json::Index::new(node as u32)).collect()));
+            }
+            root.scene = self.scene.map(|scene| json::Index::new(scene as u32));
+        }
+
+        for material in &self.materials
+        {
+            let Vec4(r, g, b, a) = material.base_color;
+            let Vec3(er, eg, eb) = material.emissive;
+            let (alpha_mode, alpha_cutoff) = match material.alpha_mode
+            {
+                AlphaMode::Opaque => (json::material::AlphaMode::Opaque, None),
+                AlphaMode::Mask(cutoff) => (json::material::AlphaMode::Mask, Some(json::material::AlphaCutoff(cutoff))),
+                AlphaMode::Blend => (json::material::AlphaMode::Blend, None)
+            };
+            finite("material factors", &[r, g, b, a, er, eg, eb, material.metallic, material.roughness, alpha_cutoff.map_or(0.0, |cutoff| cutoff.0)])?;
+            root.push(json::Material
+            {
+                alpha_cutoff,
+                alpha_mode: Valid(alpha_mode),
+                double_sided: material.double_sided,
+                name: material.name.clone(),
+                pbr_metallic_roughness: json::material::PbrMetallicRoughness
+                {
+                    base_color_factor: json::material::PbrBaseColorFactor([r, g, b, a]),
+                    base_color_texture: None,
+                    metallic_factor: json::material::StrengthFactor(material.metallic),
+                    roughness_factor: json::material::StrengthFactor(material.roughness),
+                    metallic_roughness_texture: None,
+                    extensions: None,
+                    extras: Default::default()
+                },
+                normal_texture: None,
+                occlusion_texture: None,
+                emissive_texture: None,
+                emissive_factor: json::material::EmissiveFactor([er, eg, eb]),
+                extensions: None,
+                extras: Default::default()
+            });
+        }
+
+        if !bin.is_empty()
+        {
+            root.push(json::Buffer { byte_length: bin.len().into(), name: None, uri: bin_uri.map(str::to_owned), extensions: None, extras: Default::default() });
+        }
+        Ok((root, bin))
+    }
+}
+
+//json has no NaN or infinity
+fn finite(what: &'static str, floats: &[f32]) -> Result<(), Error>
+{
+    if floats.iter().all(|float| float.is_finite()) { Ok(()) } else { Err(Error::NonFinite(what)) }
+}
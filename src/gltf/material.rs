@@ -0,0 +1,356 @@
+use super::*;
+use ::gltf::json::Value;
+
+#[derive(Debug, Clone)]
+pub enum ImageSource
+{
+    Uri(String), //external file, relative to the glTF
+    Bytes(Vec<u8>) //embedded in a buffer view or a data URI, still encoded (png, jpg, ...)
+}
+
+#[derive(Debug, Clone)]
+pub struct Image
+{
+    pub name: Option<String>,
+    pub mime_type: Option<String>,
+    pub source: ImageSource
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter
+{
+    Nearest,
+    Linear
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wrap
+{
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge
+}
+
+//None means the renderer may choose
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampler
+{
+    pub mag_filter: Option<Filter>,
+    pub min_filter: Option<Filter>,
+    pub mipmap_filter: Option<Filter>, //no mipmaps if min_filter is given but this is None
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap
+}
+
+#[derive(Debug, Clone)]
+pub struct Texture
+{
+    pub name: Option<String>,
+    pub image: usize, //index into Model::images
+    pub sampler: Sampler
+}
+
+//KHR_texture_transform: uv' = translation(offset) * rotation(rotation) * scale(scale) * uv
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureTransform
+{
+    pub offset: Vec2,
+    pub rotation: f32, //counter-clockwise in radians
+    pub scale: Vec2,
+    pub tex_coord: Option<u32> //overrides TextureInfo::tex_coord
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureInfo
+{
+    pub texture: usize, //index into Model::textures
+    pub tex_coord: u32, //uv set
+    pub transform: Option<TextureTransform>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode
+{
+    Opaque,
+    Mask(f32), //alpha cutoff
+    Blend
+}
+
+//KHR_materials_transmission
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transmission
+{
+    pub factor: f32,
+    pub texture: Option<TextureInfo> //r
+}
+
+//KHR_materials_volume
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Volume
+{
+    pub thickness: f32,
+    pub thickness_texture: Option<TextureInfo>, //g
+    pub attenuation_distance: f32, //infinite by default
+    pub attenuation_color: Vec3
+}
+
+//KHR_materials_specular
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Specular
+{
+    pub factor: f32,
+    pub texture: Option<TextureInfo>, //a
+    pub color: Vec3,
+    pub color_texture: Option<TextureInfo> //rgb
+}
+
+//KHR_materials_clearcoat
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clearcoat
+{
+    pub factor: f32,
+    pub texture: Option<TextureInfo>, //r
+    pub roughness: f32,
+    pub roughness_texture: Option<TextureInfo>, //g
+    pub normal_texture: Option<TextureInfo>
+}
+
+//KHR_materials_sheen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sheen
+{
+    pub color: Vec3,
+    pub color_texture: Option<TextureInfo>, //rgb
+    pub roughness: f32,
+    pub roughness_texture: Option<TextureInfo> //a
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material
+{
+    pub name: Option<String>,
+    pub base_color: Vec4,
+    pub base_color_texture: Option<TextureInfo>,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub metallic_roughness_texture: Option<TextureInfo>, //roughness in g, metallic in b
+    pub normal_texture: Option<TextureInfo>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<TextureInfo>, //r
+    pub occlusion_strength: f32,
+    pub emissive: Vec3,
+    pub emissive_texture: Option<TextureInfo>,
+    pub emissive_strength: f32, //KHR_materials_emissive_strength
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+    pub unlit: bool, //KHR_materials_unlit
+    pub ior: f32, //KHR_materials_ior
+    pub transmission: Option<Transmission>,
+    pub volume: Option<Volume>,
+    pub specular: Option<Specular>,
+    pub clearcoat: Option<Clearcoat>,
+    pub sheen: Option<Sheen>
+}
+
+//the glTF default material
+impl Default for Material
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            name: None,
+            base_color: Vec4(1.0, 1.0, 1.0, 1.0),
+            base_color_texture: None,
+            metallic: 1.0,
+            roughness: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive: Vec3::zero(),
+            emissive_texture: None,
+            emissive_strength: 1.0,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+            unlit: false,
+            ior: 1.5,
+            transmission: None,
+            volume: None,
+            specular: None,
+            clearcoat: None,
+            sheen: None
+        }
+    }
+}
+
+pub(super) fn decode_image(image: &::gltf::Image, buffers: &Buffers) -> Result<Image, Error>
+{
+    let (mime_type, source) = match image.source()
+    {
+        image::Source::View { view, mime_type } => (Some(mime_type.to_owned()), ImageSource::Bytes(buffers.view(&view, 0)?.to_vec())),
+        image::Source::Uri { uri, mime_type } if uri.starts_with("data:") =>
+        {
+            let mime_type = mime_type.or_else(|| uri["data:".len()..].split([';', ',']).next().filter(|mime_type| !mime_type.is_empty()));
+            (mime_type.map(str::to_owned), ImageSource::Bytes(decode_data_uri(uri)?))
+        },
+        image::Source::Uri { uri, mime_type } => (mime_type.map(str::to_owned), ImageSource::Uri(uri.to_owned()))
+    };
+    Ok(Image { name: image.name().map(str::to_owned), mime_type, source })
+}
+
+pub(super) fn decode_texture(texture: &::gltf::Texture) -> Texture
+{
+    let sampler = texture.sampler();
+    let filter = |linear| if linear { Filter::Linear } else { Filter::Nearest };
+    let (min_filter, mipmap_filter) = match sampler.min_filter()
+    {
+        None => (None, None),
+        Some(texture::MinFilter::Nearest) => (Some(Filter::Nearest), None),
+        Some(texture::MinFilter::Linear) => (Some(Filter::Linear), None),
+        Some(texture::MinFilter::NearestMipmapNearest) => (Some(Filter::Nearest), Some(Filter::Nearest)),
+        Some(texture::MinFilter::LinearMipmapNearest) => (Some(Filter::Linear), Some(Filter::Nearest)),
+        Some(texture::MinFilter::NearestMipmapLinear) => (Some(Filter::Nearest), Some(Filter::Linear)),
+        Some(texture::MinFilter::LinearMipmapLinear) => (Some(Filter::Linear), Some(Filter::Linear))
+    };
+    let wrap = |wrap| match wrap
+    {
+        texture::WrappingMode::Repeat => Wrap::Repeat,
+        texture::WrappingMode::MirroredRepeat => Wrap::MirroredRepeat,
+        texture::WrappingMode::ClampToEdge => Wrap::ClampToEdge
+    };
+    Texture
+    {
+        name: texture.name().map(str::to_owned),
+        image: texture.source().index(),
+        sampler: Sampler
+        {
+            mag_filter: sampler.mag_filter().map(|mag_filter| filter(mag_filter == texture::MagFilter::Linear)),
+            min_filter,
+            mipmap_filter,
+            wrap_s: wrap(sampler.wrap_s()),
+            wrap_t: wrap(sampler.wrap_t())
+        }
+    }
+}
+
+pub(super) fn decode_material(material: &::gltf::Material) -> Material
+{
+    let pbr = material.pbr_metallic_roughness();
+    let info = |info: ::gltf::texture::Info| TextureInfo
+    {
+        texture: info.texture().index(),
+        tex_coord: info.tex_coord(),
+        transform: texture_transform(info.extension_value("KHR_texture_transform"))
+    };
+    let [r, g, b, a] = pbr.base_color_factor();
+    let [er, eg, eb] = material.emissive_factor();
+    Material
+    {
+        name: material.name().map(str::to_owned),
+        base_color: Vec4(r, g, b, a),
+        base_color_texture: pbr.base_color_texture().map(info),
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        metallic_roughness_texture: pbr.metallic_roughness_texture().map(info),
+        normal_texture: material.normal_texture().map(|normal| TextureInfo
+        {
+            texture: normal.texture().index(),
+            tex_coord: normal.tex_coord(),
+            transform: texture_transform(normal.extension_value("KHR_texture_transform"))
+        }),
+        normal_scale: material.normal_texture().map_or(1.0, |normal| normal.scale()),
+        occlusion_texture: material.occlusion_texture().map(|occlusion| TextureInfo
+        {
+            texture: occlusion.texture().index(),
+            tex_coord: occlusion.tex_coord(),
+            transform: texture_transform(occlusion.extension_value("KHR_texture_transform"))
+        }),
+        occlusion_strength: material.occlusion_texture().map_or(1.0, |occlusion| occlusion.strength()),
+        emissive: Vec3(er, eg, eb),
+        emissive_texture: material.emissive_texture().map(info),
+        emissive_strength: material.emissive_strength().unwrap_or(1.0),
+        alpha_mode: match material.alpha_mode()
+        {
+            ::gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            ::gltf::material::AlphaMode::Mask => AlphaMode::Mask(material.alpha_cutoff().unwrap_or(0.5)),
+            ::gltf::material::AlphaMode::Blend => AlphaMode::Blend
+        },
+        double_sided: material.double_sided(),
+        unlit: material.unlit(),
+        ior: material.ior().unwrap_or(1.5),
+        transmission: material.transmission().map(|transmission| Transmission
+        {
+            factor: transmission.transmission_factor(),
+            texture: transmission.transmission_texture().map(info)
+        }),
+        volume: material.volume().map(|volume| Volume
+        {
+            thickness: volume.thickness_factor(),
+            thickness_texture: volume.thickness_texture().map(info),
+            attenuation_distance: volume.attenuation_distance(),
+            attenuation_color: volume.attenuation_color().into()
+        }),
+        specular: material.specular().map(|specular| Specular
+        {
+            factor: specular.specular_factor(),
+            texture: specular.specular_texture().map(info),
+            color: specular.specular_color_factor().into(),
+            color_texture: specular.specular_color_texture().map(info)
+        }),
+        clearcoat: material.extension_value("KHR_materials_clearcoat").map(|clearcoat| Clearcoat
+        {
+            factor: float(clearcoat, "clearcoatFactor", 0.0),
+            texture: raw_texture_info(clearcoat.get("clearcoatTexture")),
+            roughness: float(clearcoat, "clearcoatRoughnessFactor", 0.0),
+            roughness_texture: raw_texture_info(clearcoat.get("clearcoatRoughnessTexture")),
+            normal_texture: raw_texture_info(clearcoat.get("clearcoatNormalTexture"))
+        }),
+        sheen: material.extension_value("KHR_materials_sheen").map(|sheen| Sheen
+        {
+            color: floats(sheen, "sheenColorFactor", [0.0; 3]).into(),
+            color_texture: raw_texture_info(sheen.get("sheenColorTexture")),
+            roughness: float(sheen, "sheenRoughnessFactor", 0.0),
+            roughness_texture: raw_texture_info(sheen.get("sheenRoughnessTexture"))
+        })
+    }
+}
+
+//the extensions below are not typed by the gltf crate
+
+fn float(value: &Value, key: &str, default: f32) -> f32
+{
+    value.get(key).and_then(Value::as_f64).map_or(default, |float| float as f32)
+}
+
+fn floats<const N: usize>(value: &Value, key: &str, default: [f32; N]) -> [f32; N]
+{
+    let Some(array) = value.get(key).and_then(Value::as_array) else { return default };
+    let mut floats = default;
+    for (float, value) in floats.iter_mut().zip(array) { *float = value.as_f64().unwrap_or(0.0) as f32; }
+    floats
+}
+
+fn texture_transform(value: Option<&Value>) -> Option<TextureTransform>
+{
+    let value = value?;
+    Some(TextureTransform
+    {
+        offset: floats(value, "offset", [0.0; 2]).into(),
+        rotation: float(value, "rotation", 0.0),
+        scale: floats(value, "scale", [1.0; 2]).into(),
+        tex_coord: value.get("texCoord").and_then(Value::as_u64).map(|tex_coord| tex_coord as u32)
+    })
+}
+
+fn raw_texture_info(value: Option<&Value>) -> Option<TextureInfo>
+{
+    let value = value?;
+    Some(TextureInfo
+    {
+        texture: value.get("index")?.as_u64()? as usize,
+        tex_coord: value.get("texCoord").and_then(Value::as_u64).unwrap_or(0) as u32,
+        transform: texture_transform(value.get("extensions").and_then(|extensions| extensions.get("KHR_texture_transform")))
+    })
+}
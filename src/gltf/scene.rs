@@ -0,0 +1,106 @@
+use super::*;
+use crate::math::{Rotor, Mat4};
+
+#[derive(Debug, Clone)]
+pub struct Node
+{
+    pub name: Option<String>,
+    pub translation: Vec3,
+    pub rotation: Rotor,
+    pub scale: Vec3,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub meshes: Range<usize>, //indices into Model::meshes, one per primitive
+    pub skin: Option<usize>, //index into Model::skins
+    pub weights: Vec<f32> //morph target weights
+}
+
+impl Node
+{
+    //applied in the order scale -> rotation -> translation
+    #[inline]
+    pub fn local_matrix(&self) -> Mat4
+    {
+        Mat4::translation(self.translation) * self.rotation.to_mat4() * Mat4::scale(self.scale)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Scene
+{
+    pub name: Option<String>,
+    pub nodes: Vec<usize> //root nodes
+}
+
+impl Model
+{
+    //world matrix of every node, indexed like Model::nodes
+    pub fn world_matrices(&self) -> Vec<Mat4>
+    {
+        world_matrices(&self.nodes, |i| self.nodes[i].local_matrix())
+    }
+}
+
+//parents are visited before their children, so every node is computed once
+pub(super) fn world_matrices(nodes: &[Node], local: impl Fn(usize) -> Mat4) -> Vec<Mat4>
+{
+    let mut world = vec![Mat4::identity(); nodes.len()];
+    let mut stack: Vec<usize> = (0..nodes.len()).filter(|&i| nodes[i].parent.is_none()).collect();
+    while let Some(i) = stack.pop()
+    {
+        let local = local(i);
+        world[i] = match nodes[i].parent
+        {
+            Some(parent) => world[parent] * local,
+            None => local
+        };
+        stack.extend_from_slice(&nodes[i].children);
+    }
+    world
+}
+
+pub(super) fn decode(doc: &Document, primitives: &[Range<usize>]) -> Result<(Vec<Node>, Vec<Scene>), Error>
+{
+    let mut nodes: Vec<Node> = doc.nodes().map(|node|
+    {
+        let ([tx, ty, tz], [x, y, z, w], [sx, sy, sz]) = node.transform().decomposed();
+        Node
+        {
+            name: node.name().map(str::to_owned),
+            translation: Vec3(tx, ty, tz),
+            rotation: Rotor::from_quaternion(x, y, z, w),
+            scale: Vec3(sx, sy, sz),
+            parent: None,
+            children: node.children().map(|child| child.index()).collect(),
+            meshes: node.mesh().map(|mesh| primitives[mesh.index()].clone()).unwrap_or_default(),
+            skin: node.skin().map(|skin| skin.index()),
+            weights: node.weights().or_else(|| node.mesh().and_then(|mesh| mesh.weights())).unwrap_or_default().to_vec()
+        }
+    }).collect();
+
+    //every node has at most one parent and the hierarchy has no cycles
+    for i in 0..nodes.len()
+    {
+        for j in 0..nodes[i].children.len()
+        {
+            let child = nodes[i].children[j];
+            if child == i || nodes[child].parent.replace(i).is_some() { return Err(Error::Hierarchy); }
+        }
+    }
+    let mut reached = 0;
+    let mut stack: Vec<usize> = (0..nodes.len()).filter(|&i| nodes[i].parent.is_none()).collect();
+    while let Some(i) = stack.pop()
+    {
+        reached += 1;
+        stack.extend_from_slice(&nodes[i].children);
+    }
+    if reached != nodes.len() { return Err(Error::Hierarchy); }
+
+    let scenes = doc.scenes().map(|scene| Scene
+    {
+        name: scene.name().map(str::to_owned),
+        nodes: scene.nodes().map(|node| node.index()).collect()
+    }).collect();
+
+    Ok((nodes, scenes))
+}
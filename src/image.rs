@@ -1,5 +1,12 @@
 #[cfg(any(feature = "jpg", feature = "png"))]
 use std::io::Cursor;
+use std::borrow::Cow;
+
+#[cfg(feature = "hdr")]
+mod hdr;
+mod process;
+
+pub use process::Filter;
 
 #[derive(Clone, Copy)]
 pub enum Format
@@ -7,7 +14,9 @@ pub enum Format
     #[cfg(feature = "jpg")]
     Jpg,
     #[cfg(feature = "png")]
-    Png
+    Png,
+    #[cfg(feature = "hdr")]
+    Hdr
 }
 
 #[derive(Clone, Copy)]
@@ -22,7 +31,8 @@ pub struct Config
 {
     format: Format,
     channel_order: ChannelOrder,
-    default_alpha: u8
+    default_alpha: u8,
+    quality: u8
 }
 
 impl Config
@@ -33,7 +43,8 @@ impl Config
         {
             format,
             channel_order: ChannelOrder::RGBA,
-            default_alpha: 255
+            default_alpha: 255,
+            quality: 90
         }
     }
 
@@ -43,95 +54,546 @@ impl Config
         self
     }
 
+    //scaled to the pixel type of the decoded image
     pub fn default_alpha(mut self, default_alpha: u8) -> Self
     {
         self.default_alpha = default_alpha;
         self
     }
+
+    //1 - 100, only used when encoding jpg
+    pub fn quality(mut self, quality: u8) -> Self
+    {
+        self.quality = quality;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum Error
+{
+    #[cfg(feature = "jpg")]
+    Jpg(zune_jpeg::errors::DecodeErrors),
+    #[cfg(feature = "jpg")]
+    JpgEncode(jpeg_encoder::EncodingError),
+    #[cfg(feature = "png")]
+    Png(zune_png::error::PngDecodeErrors),
+    #[cfg(feature = "png")]
+    PngEncode(zune_png::zune_core::bytestream::ZByteIoError),
+    #[cfg(feature = "hdr")]
+    Hdr(&'static str),
+    UnsupportedPixelFormat,
+    TooLarge,
+    Empty
+}
+
+impl std::fmt::Display for Error
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self
+        {
+            #[cfg(feature = "jpg")]
+            Self::Jpg(err) => write!(f, "jpg: {err}"),
+            #[cfg(feature = "jpg")]
+            Self::JpgEncode(err) => write!(f, "jpg: {err}"),
+            #[cfg(feature = "png")]
+            Self::Png(err) => write!(f, "png: {err}"),
+            #[cfg(feature = "png")]
+            Self::PngEncode(err) => write!(f, "png: {err:?}"),
+            #[cfg(feature = "hdr")]
+            Self::Hdr(err) => write!(f, "hdr: {err}"),
+            Self::UnsupportedPixelFormat => write!(f, "unsupported pixel format"),
+            Self::TooLarge => write!(f, "image is too large for the format"),
+            Self::Empty => write!(f, "image has no pixels")
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelType
+{
+    U8,
+    U16,
+    F32
+}
+
+//u8 and u16 are normalized to 0 - 1 as f32, f32 is clamped when converted to integers
+#[derive(Debug, Clone, PartialEq)]
+pub enum Data
+{
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    F32(Vec<f32>) //linear for hdr
+}
+
+impl Data
+{
+    pub fn pixel_type(&self) -> PixelType
+    {
+        match self
+        {
+            Self::U8(_) => PixelType::U8,
+            Self::U16(_) => PixelType::U16,
+            Self::F32(_) => PixelType::F32
+        }
+    }
+
+    pub fn len(&self) -> usize
+    {
+        match self
+        {
+            Self::U8(data) => data.len(),
+            Self::U16(data) => data.len(),
+            Self::F32(data) => data.len()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.len() == 0
+    }
+
+    pub fn to_u8(&self) -> Cow<'_, [u8]>
+    {
+        match self
+        {
+            Self::U8(data) => Cow::Borrowed(data),
+            Self::U16(data) => Cow::Owned(data.iter().map(|&value| ((value as u32 + 128) / 257) as u8).collect()),
+            Self::F32(data) => Cow::Owned(data.iter().map(|&value| (value.clamp(0.0, 1.0) * 255.0).round() as u8).collect())
+        }
+    }
+
+    pub fn to_u16(&self) -> Cow<'_, [u16]>
+    {
+        match self
+        {
+            Self::U8(data) => Cow::Owned(data.iter().map(|&value| value as u16 * 257).collect()),
+            Self::U16(data) => Cow::Borrowed(data),
+            Self::F32(data) => Cow::Owned(data.iter().map(|&value| (value.clamp(0.0, 1.0) * 65535.0).round() as u16).collect())
+        }
+    }
+
+    pub fn to_f32(&self) -> Cow<'_, [f32]>
+    {
+        match self
+        {
+            Self::U8(data) => Cow::Owned(data.iter().map(|&value| value as f32 / 255.0).collect()),
+            Self::U16(data) => Cow::Owned(data.iter().map(|&value| value as f32 / 65535.0).collect()),
+            Self::F32(data) => Cow::Borrowed(data)
+        }
+    }
+
+    pub fn convert(&self, pixel_type: PixelType) -> Self
+    {
+        match pixel_type
+        {
+            PixelType::U8 => Self::U8(self.to_u8().into_owned()),
+            PixelType::U16 => Self::U16(self.to_u16().into_owned()),
+            PixelType::F32 => Self::F32(self.to_f32().into_owned())
+        }
+    }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Image
 {
     pub width: u32,
     pub height: u32,
     pub channels: u8,
-    pub data: Vec<u8>
+    pub data: Data
 }
 
 impl Image
 {
     pub fn decode(raw: &[u8], config: Config) -> Self
     {
-        let (mut data, (width, height)) = match config.format
+        Self::try_decode(raw, config).unwrap_or_else(|err| panic!("Image::decode: {err}"))
+    }
+
+    //always returns 4 channels, grey is copied into the color channels
+    //8 bit formats decode to PixelType::U8, 16 bit png to PixelType::U16 and hdr to PixelType::F32
+    pub fn try_decode(raw: &[u8], config: Config) -> Result<Self, Error>
+    {
+        let (data, (width, height)) = match config.format
         {
             #[cfg(feature = "jpg")]
             Format::Jpg =>
             {
                 let mut decoder = zune_jpeg::JpegDecoder::new(Cursor::new(raw));
-                let data = decoder.decode().unwrap();
-                let info = decoder.info().unwrap();
-                (data, (info.width, info.height))
+                let data = decoder.decode().map_err(Error::Jpg)?;
+                let info = decoder.info().unwrap(); //set by decode
+                (Data::U8(data), (info.width as usize, info.height as usize))
             },
             #[cfg(feature = "png")]
             Format::Png =>
             {
+                use zune_png::zune_core::result::DecodingResult;
                 let options = zune_png::zune_core::options::DecoderOptions::new_fast()
                     .set_max_width(25000)
                     .set_max_height(25000);
                 let mut decoder = zune_png::PngDecoder::new_with_options(Cursor::new(raw), options);
-                decoder.decode_headers().unwrap();
-                let (width, height) = decoder.dimensions().unwrap();
-                let zune_png::zune_core::result::DecodingResult::U8(data) = decoder.decode().unwrap() else { panic!("unsupported pixel format") };
-                (data, (width as u16, height as u16))
+                decoder.decode_headers().map_err(Error::Png)?;
+                let dimensions = decoder.dimensions().unwrap(); //set by decode_headers
+                let data = match decoder.decode().map_err(Error::Png)?
+                {
+                    DecodingResult::U8(data) => Data::U8(data),
+                    DecodingResult::U16(data) => Data::U16(data),
+                    _ => return Err(Error::UnsupportedPixelFormat)
+                };
+                (data, dimensions)
+            },
+            #[cfg(feature = "hdr")]
+            Format::Hdr =>
+            {
+                let (data, width, height) = hdr::decode(raw).map_err(Error::Hdr)?;
+                (Data::F32(data), (width, height))
             }
         };
-        let num_pixels = width as usize * height as usize;
+        let num_pixels = width * height;
+        if num_pixels == 0 { return Err(Error::Empty); }
         let components = data.len() / num_pixels;
-        if components == 1
+        if !matches!(components, 1..=4) || data.len() != num_pixels * components { return Err(Error::UnsupportedPixelFormat); }
+
+        let alpha = config.default_alpha;
+        let swap = matches!(config.channel_order, ChannelOrder::BGRA);
+        let data = match data
+        {
+            Data::U8(data) => Data::U8(to_rgba(data, components, alpha, swap)),
+            Data::U16(data) => Data::U16(to_rgba(data, components, alpha as u16 * 257, swap)),
+            Data::F32(data) => Data::F32(to_rgba(data, components, alpha as f32 / 255.0, swap))
+        };
+
+        Ok(Self
+        {
+            width: width as u32,
+            height: height as u32,
+            channels: 4,
+            data
+        })
+    }
+
+    pub fn encode(&self, config: Config) -> Vec<u8>
+    {
+        self.try_encode(config).unwrap_or_else(|err| panic!("Image::encode: {err}"))
+    }
+
+    //1 channel is written as grey, 2 as grey and alpha, 3 as RGB and 4 as RGBA, jpg and hdr drop the alpha channel
+    //png is written with 8 bits for PixelType::U8 and 16 bits otherwise, jpg converts to u8 and hdr to f32
+    //jpg subsamples the chroma channels 4:2:0
+    //with ChannelOrder::BGRA the data is expected in that order, default_alpha is ignored
+    pub fn try_encode(&self, config: Config) -> Result<Vec<u8>, Error>
+    {
+        let num_pixels = self.width as usize * self.height as usize;
+        let channels = self.channels as usize;
+        if num_pixels == 0 { return Err(Error::Empty); }
+        if !matches!(channels, 1..=4) || self.data.len() != num_pixels * channels { return Err(Error::UnsupportedPixelFormat); }
+        let swap = matches!(config.channel_order, ChannelOrder::BGRA) && channels >= 3;
+        #[cfg(any(feature = "png", feature = "hdr"))]
+        fn reorder<T: Clone>(mut data: Cow<'_, [T]>, channels: usize, swap: bool) -> Cow<'_, [T]>
+        {
+            if swap { for pixel in data.to_mut().chunks_exact_mut(channels) { pixel.swap(0, 2); } }
+            data
+        }
+
+        match config.format
         {
-            let mut new_data = vec![config.default_alpha; num_pixels * 4];
-            for (grey, [r_new, g_new, b_new, _]) in data.iter().zip(new_data.iter_mut().array_chunks())
+            #[cfg(feature = "jpg")]
+            Format::Jpg =>
             {
-                *r_new = *grey;
-                *g_new = *grey;
-                *b_new = *grey;
-            }
-            data = new_data;
+                use jpeg_encoder::{Encoder, ColorType, SamplingFactor};
+                if self.width > u16::MAX as u32 || self.height > u16::MAX as u32 { return Err(Error::TooLarge); }
+                let data = self.data.to_u8();
+                let (data, color_type) = match (channels, swap)
+                {
+                    (1, _) => (data, ColorType::Luma),
+                    (2, _) => (data.chunks_exact(2).map(|pixel| pixel[0]).collect(), ColorType::Luma),
+                    (3, false) => (data, ColorType::Rgb),
+                    (3, true) => (data, ColorType::Bgr),
+                    (_, false) => (data, ColorType::Rgba),
+                    (_, true) => (data, ColorType::Bgra)
+                };
+                let mut bytes = Vec::new();
+                let mut encoder = Encoder::new(&mut bytes, config.quality.clamp(1, 100));
+                encoder.set_sampling_factor(SamplingFactor::F_2_2);
+                encoder.encode(&data, self.width as u16, self.height as u16, color_type).map_err(Error::JpgEncode)?;
+                Ok(bytes)
+            },
+            #[cfg(feature = "png")]
+            Format::Png =>
+            {
+                use zune_png::zune_core::{bit_depth::BitDepth, colorspace::ColorSpace, options::EncoderOptions};
+                let colorspace = match channels
+                {
+                    1 => ColorSpace::Luma,
+                    2 => ColorSpace::LumaA,
+                    3 => ColorSpace::RGB,
+                    _ => ColorSpace::RGBA
+                };
+                //16 bit samples are expected in big endian
+                let (data, depth) = match &self.data
+                {
+                    Data::U8(data) => (reorder(Cow::Borrowed(data), channels, swap).into_owned(), BitDepth::Eight),
+                    data => (reorder(data.to_u16(), channels, swap).iter().flat_map(|value| value.to_be_bytes()).collect(), BitDepth::Sixteen)
+                };
+                let options = EncoderOptions::new(self.width as usize, self.height as usize, colorspace, depth);
+                let mut bytes = Vec::new();
+                zune_png::PngEncoder::new(&data, options).encode(&mut bytes).map_err(Error::PngEncode)?;
+                Ok(bytes)
+            },
+            #[cfg(feature = "hdr")]
+            Format::Hdr => Ok(hdr::encode(self.width as usize, self.height as usize, channels, &reorder(self.data.to_f32(), channels, swap)))
+        }
+    }
+
+    pub fn pixel_type(&self) -> PixelType
+    {
+        self.data.pixel_type()
+    }
+
+    pub fn convert(&mut self, pixel_type: PixelType)
+    {
+        if self.pixel_type() != pixel_type { self.data = self.data.convert(pixel_type); }
+    }
+
+    pub fn extract_channel(&mut self, channel: u8)
+    {
+        if self.channels != 4 { panic!("no 4 channels"); }
+        fn extract<T: Copy>(data: &[T], channel: u8) -> Vec<T>
+        {
+            data.chunks_exact(4).map(|pixel| pixel[channel as usize]).collect()
         }
-        if components == 3
+        self.data = match &self.data
+        {
+            Data::U8(data) => Data::U8(extract(data, channel)),
+            Data::U16(data) => Data::U16(extract(data, channel)),
+            Data::F32(data) => Data::F32(extract(data, channel))
+        };
+        self.channels = 1;
+    }
+}
+
+//expands 1 - 3 components to 4, grey is copied into the color channels
+fn to_rgba<T: Copy>(data: Vec<T>, components: usize, alpha: T, swap: bool) -> Vec<T>
+{
+    let mut data = match components
+    {
+        1 => data.into_iter().flat_map(|grey| [grey, grey, grey, alpha]).collect(),
+        2 => data.chunks_exact(2).flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]]).collect(),
+        3 => data.chunks_exact(3).flat_map(|pixel| [pixel[0], pixel[1], pixel[2], alpha]).collect(),
+        _ => data
+    };
+    if swap { for pixel in data.chunks_exact_mut(4) { pixel.swap(0, 2); } }
+    data
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    //a smooth gradient with some noise, 4 channels
+    #[cfg(any(feature = "jpg", feature = "png"))]
+    fn gradient(width: u32, height: u32) -> Image
+    {
+        let mut data = Vec::new();
+        for y in 0..height
         {
-            let mut new_data = vec![config.default_alpha; num_pixels * 4];
-            for ([r, g, b], [r_new, g_new, b_new, _]) in data.iter().array_chunks().zip(new_data.iter_mut().array_chunks())
+            for x in 0..width
             {
-                *r_new = *r;
-                *g_new = *g;
-                *b_new = *b;
+                data.extend_from_slice(&[(x * 255 / width) as u8, (y * 255 / height) as u8, ((x + y) * 3 % 7 + 100) as u8, (x * y % 256) as u8]);
             }
-            data = new_data;
         }
-        if matches!(config.channel_order, ChannelOrder::BGRA)
+        Image { width, height, channels: 4, data: Data::U8(data) }
+    }
+
+    #[cfg(any(feature = "jpg", feature = "png"))]
+    fn u8s(image: &Image) -> &[u8]
+    {
+        let Data::U8(data) = &image.data else { panic!("not u8") };
+        data
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn encode_png()
+    {
+        let rgba = gradient(37, 21);
+        let rgb = Image { channels: 3, data: Data::U8(u8s(&rgba).chunks_exact(4).flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect()), ..rgba };
+        let grey_alpha = Image { channels: 2, data: Data::U8(u8s(&rgba).chunks_exact(4).flat_map(|pixel| [pixel[1], pixel[3]]).collect()), ..rgba };
+        let grey = { let mut grey = gradient(37, 21); grey.extract_channel(1); grey };
+        for image in [&rgba, &rgb, &grey_alpha, &grey]
         {
-            for [r, _, b, _] in data.iter_mut().array_chunks()
+            let decoded = Image::decode(&image.encode(Config::new(Format::Png)), Config::new(Format::Png).default_alpha(7));
+            assert_eq!((decoded.width, decoded.height, decoded.channels, decoded.pixel_type()), (37, 21, 4, PixelType::U8));
+            for (pixel, decoded) in u8s(image).chunks_exact(image.channels as usize).zip(u8s(&decoded).chunks_exact(4))
             {
-                std::mem::swap(r, b);
+                match image.channels
+                {
+                    1 => assert_eq!(decoded, [pixel[0], pixel[0], pixel[0], 7]),
+                    2 => assert_eq!(decoded, [pixel[0], pixel[0], pixel[0], pixel[1]]),
+                    3 => assert_eq!(decoded, [pixel[0], pixel[1], pixel[2], 7]),
+                    _ => assert_eq!(decoded, pixel)
+                }
             }
         }
 
-        Self
+        //channel order is applied on the way out and back in
+        let bgra = Config::new(Format::Png).channel_order(ChannelOrder::BGRA);
+        let decoded = Image::decode(&rgba.encode(bgra), Config::new(Format::Png));
+        assert!(u8s(&rgba).chunks_exact(4).zip(u8s(&decoded).chunks_exact(4)).all(|(a, b)| a == [b[2], b[1], b[0], b[3]]));
+        assert!(matches!(Image { width: 2, height: 2, channels: 4, data: Data::U8(vec![0; 3]) }.try_encode(Config::new(Format::Png)), Err(Error::UnsupportedPixelFormat)));
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn png_16_bit()
+    {
+        //a heightmap with values that do not fit into 8 bits
+        let heights: Vec<u16> = (0..64 * 48).map(|i| (i * 21 + 3) as u16).collect();
+        let image = Image { width: 64, height: 48, channels: 1, data: Data::U16(heights.clone()) };
+        let mut decoded = Image::decode(&image.encode(Config::new(Format::Png)), Config::new(Format::Png));
+        assert_eq!(decoded.pixel_type(), PixelType::U16);
+        let Data::U16(data) = &decoded.data else { unreachable!() };
+        assert!(data.chunks_exact(4).zip(&heights).all(|(pixel, &height)| pixel == [height, height, height, 65535]));
+
+        decoded.extract_channel(0);
+        decoded.convert(PixelType::F32);
+        let Data::F32(data) = &decoded.data else { unreachable!() };
+        assert!(data.iter().zip(&heights).all(|(&float, &height)| (float * 65535.0 - height as f32).abs() < 0.01));
+
+        //f32 is written as 16 bit
+        let decoded = Image::decode(&decoded.encode(Config::new(Format::Png)), Config::new(Format::Png));
+        let Data::U16(data) = &decoded.data else { unreachable!() };
+        assert!(data.chunks_exact(4).zip(&heights).all(|(pixel, &height)| pixel[0] == height));
+    }
+
+    #[test]
+    fn conversions()
+    {
+        let data = Data::U8((0..=255).collect());
+        assert_eq!(data.convert(PixelType::F32).convert(PixelType::U8), data);
+        assert_eq!(data.convert(PixelType::U16).convert(PixelType::U8), data);
+        assert_eq!(data.to_u16()[..], (0..=255).map(|value| value * 257).collect::<Vec<u16>>()[..]);
+        assert_eq!(Data::U16(vec![0, 32767, 65535]).to_f32()[..], [0.0, 32767.0 / 65535.0, 1.0]);
+        assert_eq!(Data::F32(vec![-1.0, 0.5, 4.0]).to_u8()[..], [0, 128, 255]);
+    }
+
+    #[cfg(feature = "jpg")]
+    #[test]
+    fn encode_jpg()
+    {
+        let rgba = gradient(45, 30);
+        let mut grey = gradient(45, 30);
+        grey.extract_channel(0);
+        for image in [&rgba, &grey]
         {
-            width: width as u32,
-            height: height as u32,
-            channels: 4,
-            data
+            let high = image.encode(Config::new(Format::Jpg).quality(95));
+            let low = image.encode(Config::new(Format::Jpg).quality(10));
+            assert!(low.len() < high.len());
+
+            let decoded = Image::decode(&high, Config::new(Format::Jpg));
+            assert_eq!((decoded.width, decoded.height), (45, 30));
+            let channels = if image.channels == 1 { 1 } else { 3 };
+            let error = u8s(image).chunks_exact(image.channels as usize).zip(u8s(&decoded).chunks_exact(4))
+                .flat_map(|(pixel, decoded)| (0..channels).map(|c| (pixel[c] as i32 - decoded[c] as i32).abs()))
+                .max().unwrap();
+            assert!(error <= 12, "{error}");
+
+            //with color the luma component is sampled 2x2, so chroma is subsampled 4:2:0
+            let sof = high.windows(2).position(|marker| marker == [0xFF, 0xC0]).unwrap();
+            assert_eq!((high[sof + 9], high[sof + 11]), if image.channels == 1 { (1, 0x11) } else { (3, 0x22) });
         }
+
+        //odd sizes, not a multiple of the 16x16 blocks, in BGRA order
+        let mut bgra = gradient(37, 21);
+        let Data::U8(data) = &mut bgra.data else { unreachable!() };
+        for pixel in data.chunks_exact_mut(4) { pixel.swap(0, 2); }
+        let decoded = Image::decode(&bgra.encode(Config::new(Format::Jpg).channel_order(ChannelOrder::BGRA).quality(95)), Config::new(Format::Jpg));
+        assert_eq!((decoded.width, decoded.height), (37, 21));
+        let error = u8s(&gradient(37, 21)).chunks_exact(4).zip(u8s(&decoded).chunks_exact(4))
+            .flat_map(|(pixel, decoded)| (0..3).map(|c| (pixel[c] as i32 - decoded[c] as i32).abs()))
+            .max().unwrap();
+        assert!(error <= 24, "{error}");
     }
 
-    pub fn extract_channel(&mut self, channel: u8)
+    #[cfg(feature = "hdr")]
+    #[test]
+    fn hdr()
     {
-        if self.channels != 4 { panic!("no 4 channels"); }
-        let mut data = Vec::with_capacity((self.width * self.height) as usize);
-        for pixels in self.data.iter().array_chunks::<4>() { data.push(*pixels[channel as usize]); }
-        self.channels = 1;
-        self.data = data;
+        //wide enough for run length encoded scanlines, with values far above 1
+        for width in [5, 33]
+        {
+            let data: Vec<f32> = (0..width * 7 * 3).map(|i| if i % 11 == 0 { 0.0 } else { (i as f32 * 0.37).exp2() * 0.01 }).collect();
+            let image = Image { width: width as u32, height: 7, channels: 3, data: Data::F32(data.clone()) };
+            let decoded = Image::decode(&image.encode(Config::new(Format::Hdr)), Config::new(Format::Hdr));
+            assert_eq!((decoded.width, decoded.height, decoded.channels), (width as u32, 7, 4));
+            let Data::F32(decoded) = &decoded.data else { panic!("not f32") };
+            for (pixel, decoded) in data.chunks_exact(3).zip(decoded.chunks_exact(4))
+            {
+                //rgbe shares one exponent, so the precision is relative to the brightest channel
+                let max = pixel.iter().fold(0.0f32, |max, &value| max.max(value));
+                assert!((0..3).all(|c| (pixel[c] - decoded[c]).abs() <= max / 128.0), "{pixel:?} {decoded:?}");
+                assert_eq!(decoded[3], 1.0);
+            }
+        }
+        for raw in [&b"#?RADIANCE\n\n+Y 1 +X 1\n\0\0\0\0"[..], b"#?RADIANCE\n\n-Y 25000 +X 25000\n"]
+        {
+            assert!(matches!(Image::try_decode(raw, Config::new(Format::Hdr)), Err(Error::Hdr(_))));
+        }
+    }
+
+    #[test]
+    fn processing()
+    {
+        let u8s = |image: &Image| { let Data::U8(data) = &image.data else { panic!("not u8") }; data.clone() };
+
+        //box downscaling averages, constant images stay constant with every filter
+        let image = Image { width: 4, height: 2, channels: 1, data: Data::U8(vec![0, 10, 20, 30, 40, 50, 60, 70]) };
+        assert_eq!(u8s(&image.resize(2, 1, Filter::Box, false)), [25, 45]);
+        let constant = Image { width: 3, height: 5, channels: 2, data: Data::U16(vec![1000; 30]) };
+        for filter in [Filter::Box, Filter::Bilinear, Filter::Lanczos]
+        {
+            for (width, height) in [(7, 11), (2, 2), (1, 9)]
+            {
+                let resized = constant.resize(width, height, filter, true);
+                assert_eq!((resized.width, resized.height, resized.channels), (width, height, 2));
+                assert!(resized.data.to_u16().iter().all(|&value| value.abs_diff(1000) <= 1), "{filter:?}");
+            }
+        }
+
+        //black and white average to mid grey in linear space, which is brighter in srgb
+        let image = Image { width: 2, height: 1, channels: 4, data: Data::U8(vec![0, 0, 0, 255, 255, 255, 255, 255]) };
+        let mip = image.resize(1, 1, Filter::Box, true);
+        let grey = (crate::color::rgb2srgb(0.5) * 255.0).round() as u8;
+        assert_eq!(u8s(&mip), [grey, grey, grey, 255]);
+        assert_eq!(u8s(&image.resize(1, 1, Filter::Box, false)), [128, 128, 128, 255]);
+
+        let image = Image { width: 5, height: 3, channels: 3, data: Data::F32((0..45).map(|i| i as f32).collect()) };
+        let mips = image.mipmaps(Filter::Box, false);
+        assert_eq!(mips.iter().map(|mip| (mip.width, mip.height)).collect::<Vec<_>>(), [(5, 3), (2, 1), (1, 1)]);
+        assert_eq!(mips[0], image);
+        assert!(mips.iter().all(|mip| mip.pixel_type() == PixelType::F32 && mip.data.len() == (mip.width * mip.height * 3) as usize));
+
+        let mut image = Image { width: 2, height: 3, channels: 1, data: Data::U8(vec![1, 2, 3, 4, 5, 6]) };
+        image.flip_vertical();
+        assert_eq!(u8s(&image), [5, 6, 3, 4, 1, 2]);
+        assert_eq!(u8s(&image.crop(1, 1, 1, 2)), [4, 2]);
+        for (x, y) in [(1, u32::MAX), (u32::MAX, 1)]
+        {
+            let err = std::panic::catch_unwind(|| image.crop(x, y, 1, 1)).unwrap_err();
+            assert_eq!(err.downcast_ref::<&str>(), Some(&"Image::crop: out of bounds"));
+        }
+
+        //blitting clips at the border and converts the pixel type
+        let mut target = Image { width: 3, height: 3, channels: 1, data: Data::U8(vec![0; 9]) };
+        target.blit(&Image { width: 2, height: 2, channels: 1, data: Data::F32(vec![1.0, 0.5, 0.0, 1.0]) }, 2, 1);
+        assert_eq!(u8s(&target), [0, 0, 0, 0, 0, 255, 0, 0, 0]);
+
+        let mut image = Image { width: 2, height: 1, channels: 4, data: Data::U8(vec![200, 100, 50, 128, 255, 255, 255, 0]) };
+        image.premultiply();
+        assert_eq!(u8s(&image), [100, 50, 25, 128, 0, 0, 0, 0]);
     }
 }
@@ -0,0 +1,131 @@
+//radiance rgbe images, the usual format for environment maps
+//only the standard orientation (-Y height +X width) and the rgbe pixel format are read
+
+fn to_rgb([r, g, b, e]: [u8; 4]) -> [f32; 3]
+{
+    if e == 0 { return [0.0; 3]; }
+    let scale = 2f32.powi(e as i32 - (128 + 8));
+    [r as f32 * scale, g as f32 * scale, b as f32 * scale]
+}
+
+fn to_rgbe([r, g, b]: [f32; 3]) -> [u8; 4]
+{
+    let max = r.max(g).max(b);
+    if max.is_nan() || max < 1e-32 { return [0; 4]; }
+    let max = max.min(f32::MAX);
+    //max = mantissa * 2^exponent with mantissa in [0.5, 1)
+    let mut exponent = max.log2().floor() as i32 + 1;
+    if max / 2f32.powi(exponent) >= 1.0 { exponent += 1; }
+    if max / 2f32.powi(exponent) < 0.5 { exponent -= 1; }
+    let scale = 256.0 / 2f32.powi(exponent);
+    let channel = |value: f32| (value.max(0.0) * scale).min(255.0) as u8;
+    [channel(r), channel(g), channel(b), (exponent + 128) as u8]
+}
+
+//rgb floats, width, height
+pub(super) fn decode(raw: &[u8]) -> Result<(Vec<f32>, usize, usize), &'static str>
+{
+    let mut lines = raw.split(|&byte| byte == b'\n');
+    let mut offset = 0;
+    let mut line = || lines.next().inspect(|line| offset += line.len() + 1).ok_or("unexpected end of header");
+
+    let magic = line()?;
+    if !magic.starts_with(b"#?RADIANCE") && !magic.starts_with(b"#?RGBE") { return Err("not a radiance file"); }
+    loop
+    {
+        let line = line()?;
+        if line.is_empty() { break; }
+        if line.starts_with(b"FORMAT=") && line != b"FORMAT=32-bit_rle_rgbe" { return Err("unsupported pixel format"); }
+    }
+    let resolution = std::str::from_utf8(line()?).map_err(|_| "invalid resolution")?;
+    let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..]
+    {
+        ["-Y", height, "+X", width] => (height.parse::<usize>().map_err(|_| "invalid resolution")?, width.parse::<usize>().map_err(|_| "invalid resolution")?),
+        _ => return Err("unsupported orientation")
+    };
+    if width == 0 || height == 0 { return Ok((Vec::new(), width, height)); }
+    if width > 25000 || height > 25000 { return Err("image is too large"); }
+
+    let mut bytes = raw[offset.min(raw.len())..].iter().copied();
+    let mut next = || bytes.next().ok_or("unexpected end of pixels");
+    //every scanline takes at least 4 bytes, a truncated file must not reserve the size its header claims
+    let mut data = Vec::with_capacity(width * 3 * height.min(raw.len().saturating_sub(offset) / 4));
+    let mut scanline = vec![[0; 4]; width];
+    for _ in 0..height
+    {
+        let first = [next()?, next()?, next()?, next()?];
+        if (8..=0x7FFF).contains(&width) && first[0] == 2 && first[1] == 2 && first[2] < 128
+        {
+            //run length encoded, one component after another
+            if ((first[2] as usize) << 8 | first[3] as usize) != width { return Err("invalid scanline length"); }
+            for component in 0..4
+            {
+                let mut x = 0;
+                while x < width
+                {
+                    let count = next()? as usize;
+                    let (count, run) = if count > 128 { (count - 128, true) } else { (count, false) };
+                    if count == 0 || x + count > width { return Err("invalid run length"); }
+                    let value = if run { next()? } else { 0 };
+                    for pixel in &mut scanline[x..x + count] { pixel[component] = if run { value } else { next()? }; }
+                    x += count;
+                }
+            }
+        }
+        else
+        {
+            //flat pixels, (1, 1, 1, n) repeats the previous pixel
+            let mut x = 0;
+            let mut shift = 0;
+            let mut pixel = first;
+            loop
+            {
+                if pixel[..3] == [1, 1, 1] && x > 0
+                {
+                    let count = (pixel[3] as usize) << shift;
+                    if x + count > width { return Err("invalid run length"); }
+                    let previous = scanline[x - 1];
+                    scanline[x..x + count].fill(previous);
+                    x += count;
+                    shift += 8;
+                }
+                else
+                {
+                    scanline[x] = pixel;
+                    x += 1;
+                    shift = 0;
+                }
+                if x == width { break; }
+                pixel = [next()?, next()?, next()?, next()?];
+            }
+        }
+        data.extend(scanline.iter().flat_map(|&pixel| to_rgb(pixel)));
+    }
+    Ok((data, width, height))
+}
+
+//1 and 2 channels are written as grey, alpha is dropped
+pub(super) fn encode(width: usize, height: usize, channels: usize, data: &[f32]) -> Vec<u8>
+{
+    let mut bytes = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {height} +X {width}\n").into_bytes();
+    for row in data.chunks_exact(width * channels)
+    {
+        let scanline: Vec<[u8; 4]> = row.chunks_exact(channels).map(|pixel| to_rgbe(if channels < 3 { [pixel[0]; 3] } else { [pixel[0], pixel[1], pixel[2]] })).collect();
+        if !(8..=0x7FFF).contains(&width)
+        {
+            bytes.extend(scanline.iter().flatten());
+            continue;
+        }
+        //run length encoding with literal runs only, a flat scanline could be mistaken for an encoded one
+        bytes.extend_from_slice(&[2, 2, (width >> 8) as u8, width as u8]);
+        for component in 0..4
+        {
+            for literal in scanline.chunks(128)
+            {
+                bytes.push(literal.len() as u8);
+                bytes.extend(literal.iter().map(|pixel| pixel[component]));
+            }
+        }
+    }
+    bytes
+}
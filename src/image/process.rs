@@ -0,0 +1,236 @@
+use super::*;
+use crate::color::{srgb2rgb, rgb2srgb};
+
+//filtering works on f32 and converts back to the pixel type of the image
+//with srgb the color channels are filtered in linear space, alpha is always treated as linear
+//alpha is not taken into account, premultiply first to avoid dark fringes around transparent pixels
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter
+{
+    Box, //nearest when upscaling
+    Bilinear,
+    Lanczos //3 lobes, can overshoot
+}
+
+impl Filter
+{
+    fn radius(self) -> f32
+    {
+        match self
+        {
+            Self::Box => 0.5,
+            Self::Bilinear => 1.0,
+            Self::Lanczos => 3.0
+        }
+    }
+
+    fn weight(self, x: f32) -> f32
+    {
+        let sinc = |x: f32| if x == 0.0 { 1.0 } else { (x * std::f32::consts::PI).sin() / (x * std::f32::consts::PI) };
+        match self
+        {
+            Self::Box => if (-0.5..0.5).contains(&x) { 1.0 } else { 0.0 },
+            Self::Bilinear => (1.0 - x.abs()).max(0.0),
+            Self::Lanczos => if x.abs() < 3.0 { sinc(x) * sinc(x / 3.0) } else { 0.0 }
+        }
+    }
+}
+
+//source pixels and normalized weights for every target pixel along one axis
+fn contributions(source: usize, target: usize, filter: Filter) -> Vec<Vec<(usize, f32)>>
+{
+    let scale = target as f32 / source as f32;
+    let stretch = scale.min(1.0); //the filter is widened when downscaling
+    let support = filter.radius() / stretch;
+    (0..target).map(|i|
+    {
+        let center = (i as f32 + 0.5) / scale;
+        let first = (center - support - 0.5).floor() as isize;
+        let last = (center + support - 0.5).ceil() as isize;
+        let mut weights: Vec<(usize, f32)> = (first..=last)
+            .map(|j| (j.clamp(0, source as isize - 1) as usize, filter.weight((j as f32 + 0.5 - center) * stretch)))
+            .filter(|&(_, weight)| weight != 0.0)
+            .collect();
+        let sum: f32 = weights.iter().map(|&(_, weight)| weight).sum();
+        if sum == 0.0 { return vec![((center as usize).min(source - 1), 1.0)]; }
+        for (_, weight) in &mut weights { *weight /= sum; }
+        weights
+    }).collect()
+}
+
+//separable, horizontal then vertical
+fn resample(data: &[f32], (width, height): (usize, usize), channels: usize, (new_width, new_height): (usize, usize), filter: Filter) -> Vec<f32>
+{
+    let mut horizontal = vec![0.0; new_width * height * channels];
+    let columns = contributions(width, new_width, filter);
+    for (row, new_row) in data.chunks_exact(width * channels).zip(horizontal.chunks_exact_mut(new_width * channels))
+    {
+        for (pixel, weights) in new_row.chunks_exact_mut(channels).zip(&columns)
+        {
+            for &(x, weight) in weights
+            {
+                for (value, source) in pixel.iter_mut().zip(&row[x * channels..(x + 1) * channels]) { *value += source * weight; }
+            }
+        }
+    }
+
+    let mut vertical = vec![0.0; new_width * new_height * channels];
+    let stride = new_width * channels;
+    for (new_row, weights) in vertical.chunks_exact_mut(stride).zip(&contributions(height, new_height, filter))
+    {
+        for &(y, weight) in weights
+        {
+            for (value, source) in new_row.iter_mut().zip(&horizontal[y * stride..(y + 1) * stride]) { *value += source * weight; }
+        }
+    }
+    vertical
+}
+
+//grey or rgb, everything after is alpha
+fn color_channels(channels: usize) -> usize
+{
+    if channels < 3 { 1 } else { 3 }
+}
+
+impl Image
+{
+    fn unpack(&self, srgb: bool) -> Vec<f32>
+    {
+        let mut data = self.data.to_f32().into_owned();
+        let (channels, colors) = (self.channels as usize, color_channels(self.channels as usize));
+        if srgb { for pixel in data.chunks_exact_mut(channels) { for value in &mut pixel[..colors] { *value = srgb2rgb(*value); } } }
+        data
+    }
+
+    //the inverse of unpack, keeps the pixel type of self
+    fn pack(&self, (width, height): (usize, usize), mut data: Vec<f32>, srgb: bool) -> Self
+    {
+        let (channels, colors) = (self.channels as usize, color_channels(self.channels as usize));
+        if srgb { for pixel in data.chunks_exact_mut(channels) { for value in &mut pixel[..colors] { *value = rgb2srgb(*value); } } }
+        Self
+        {
+            width: width as u32,
+            height: height as u32,
+            channels: self.channels,
+            data: Data::F32(data).convert(self.pixel_type())
+        }
+    }
+
+    fn size(&self) -> (usize, usize)
+    {
+        (self.width as usize, self.height as usize)
+    }
+
+    pub fn resize(&self, width: u32, height: u32, filter: Filter, srgb: bool) -> Self
+    {
+        assert!(width > 0 && height > 0 && !self.data.is_empty(), "Image::resize: empty image");
+        let size = (width as usize, height as usize);
+        let data = resample(&self.unpack(srgb), self.size(), self.channels as usize, size, filter);
+        self.pack(size, data, srgb)
+    }
+
+    //all levels down to 1x1, starting with a copy of self, each level halves the size rounding down
+    //filtering happens on the linear level above to avoid repeated quantization
+    pub fn mipmaps(&self, filter: Filter, srgb: bool) -> Vec<Self>
+    {
+        assert!(!self.data.is_empty(), "Image::mipmaps: empty image");
+        let mut levels = vec![self.clone()];
+        let mut size = self.size();
+        let mut data = self.unpack(srgb);
+        while size != (1, 1)
+        {
+            let new_size = ((size.0 / 2).max(1), (size.1 / 2).max(1));
+            data = resample(&data, size, self.channels as usize, new_size, filter);
+            size = new_size;
+            levels.push(self.pack(size, data.clone(), srgb));
+        }
+        levels
+    }
+
+    pub fn flip_vertical(&mut self)
+    {
+        fn flip<T>(data: &mut [T], stride: usize)
+        {
+            let rows = data.len() / stride;
+            for y in 0..rows / 2
+            {
+                let (top, bottom) = data.split_at_mut((rows - 1 - y) * stride);
+                top[y * stride..(y + 1) * stride].swap_with_slice(&mut bottom[..stride]);
+            }
+        }
+        let stride = self.width as usize * self.channels as usize;
+        match &mut self.data
+        {
+            Data::U8(data) => flip(data, stride),
+            Data::U16(data) => flip(data, stride),
+            Data::F32(data) => flip(data, stride)
+        }
+    }
+
+    //multiplies the color channels with alpha, needs 2 or 4 channels
+    pub fn premultiply(&mut self)
+    {
+        let channels = self.channels as usize;
+        assert!(channels == 2 || channels == 4, "Image::premultiply: no alpha channel");
+        let mut data = self.unpack(false);
+        for pixel in data.chunks_exact_mut(channels)
+        {
+            let alpha = pixel[channels - 1];
+            for value in &mut pixel[..channels - 1] { *value *= alpha; }
+        }
+        *self = self.pack(self.size(), data, false);
+    }
+
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Self
+    {
+        let inside = |start: u32, size: u32, bound: u32| start.checked_add(size).is_some_and(|end| end <= bound);
+        assert!(inside(x, width, self.width) && inside(y, height, self.height), "Image::crop: out of bounds");
+        fn crop<T: Copy>(data: &[T], stride: usize, (x, y, width, height): (usize, usize, usize, usize), channels: usize) -> Vec<T>
+        {
+            data.chunks_exact(stride).skip(y).take(height).flat_map(|row| &row[x * channels..(x + width) * channels]).copied().collect()
+        }
+        let channels = self.channels as usize;
+        let stride = self.width as usize * channels;
+        let rect = (x as usize, y as usize, width as usize, height as usize);
+        let data = match &self.data
+        {
+            Data::U8(data) => Data::U8(crop(data, stride, rect, channels)),
+            Data::U16(data) => Data::U16(crop(data, stride, rect, channels)),
+            Data::F32(data) => Data::F32(crop(data, stride, rect, channels))
+        };
+        Self { width, height, channels: self.channels, data }
+    }
+
+    //copies source to (x, y), clipped at the right and bottom border, the pixel type of source is converted
+    pub fn blit(&mut self, source: &Image, x: u32, y: u32)
+    {
+        assert!(self.channels == source.channels, "Image::blit: channel counts differ");
+        fn blit<T: Copy>(target: &mut [T], target_width: usize, source: &[T], source_width: usize, (x, y): (usize, usize), channels: usize)
+        {
+            if x >= target_width || source_width == 0 { return; }
+            let width = source_width.min(target_width - x) * channels;
+            let rows = target.chunks_exact_mut(target_width * channels).skip(y);
+            for (target, source) in rows.zip(source.chunks_exact(source_width * channels))
+            {
+                target[x * channels..][..width].copy_from_slice(&source[..width]);
+            }
+        }
+        let converted;
+        let source_data = if source.pixel_type() == self.pixel_type() { &source.data }
+        else
+        {
+            converted = source.data.convert(self.pixel_type());
+            &converted
+        };
+        let (target_width, source_width) = (self.width as usize, source.width as usize);
+        let (position, channels) = ((x as usize, y as usize), self.channels as usize);
+        match (&mut self.data, source_data)
+        {
+            (Data::U8(target), Data::U8(source)) => blit(target, target_width, source, source_width, position, channels),
+            (Data::U16(target), Data::U16(source)) => blit(target, target_width, source, source_width, position, channels),
+            (Data::F32(target), Data::F32(source)) => blit(target, target_width, source, source_width, position, channels),
+            _ => unreachable!() //converted above
+        }
+    }
+}
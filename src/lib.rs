@@ -1,4 +1,4 @@
-#![cfg_attr(any(feature = "jpg", feature = "png", feature = "gltf"), feature(iter_array_chunks))]
+#![cfg_attr(feature = "gltf", feature(iter_array_chunks))]
 
 #[cfg(feature = "math")]
 pub mod math;
@@ -18,7 +18,7 @@ pub mod time;
 pub mod rand;
 #[cfg(feature = "color")]
 pub mod color;
-#[cfg(any(feature = "jpg", feature = "png"))]
+#[cfg(any(feature = "jpg", feature = "png", feature = "hdr"))]
 pub mod image;
 #[cfg(feature = "gltf")]
 pub mod gltf;
@@ -0,0 +1,68 @@
+use super::*;
+use std::{thread, sync::atomic::{AtomicUsize, Ordering}};
+
+//meshes bricks of (at most) chunk_resolutions cells on num_threads scoped threads
+//vertices on brick faces are merged by their grid edge, so the result equals build up to the vertex order
+pub fn build_chunked(sdf: impl Fn(Vec3) -> f32 + Sync, config: Config, chunk_resolutions: (u32, u32, u32), num_threads: usize) -> (Vec<Vec3>, Vec<u32>)
+{
+    let Config { offset, radii, resolutions, .. } = config;
+    let step_sizes = (2.0 * radii.0 / resolutions.0 as f32, 2.0 * radii.1 / resolutions.1 as f32, 2.0 * radii.2 / resolutions.2 as f32);
+    let origin = offset - radii;
+
+    let chunk_counts = (resolutions.0.div_ceil(chunk_resolutions.0), resolutions.1.div_ceil(chunk_resolutions.1), resolutions.2.div_ceil(chunk_resolutions.2));
+    let num_chunks = (chunk_counts.0 * chunk_counts.1 * chunk_counts.2) as usize;
+    let chunk = |i: usize|
+    {
+        let i = i as u32;
+        let index = (i % chunk_counts.0, (i / chunk_counts.0) % chunk_counts.1, i / (chunk_counts.0 * chunk_counts.1));
+        let cells_min = (index.0 * chunk_resolutions.0, index.1 * chunk_resolutions.1, index.2 * chunk_resolutions.2);
+        let cells_max = (resolutions.0.min(cells_min.0 + chunk_resolutions.0), resolutions.1.min(cells_min.1 + chunk_resolutions.1), resolutions.2.min(cells_min.2 + chunk_resolutions.2));
+        (cells_min, cells_max)
+    };
+
+    let next = AtomicUsize::new(0);
+    let mut meshes: Vec<_> = thread::scope(|scope|
+    {
+        let handles: Vec<_> = (0..num_threads.max(1)).map(|_| scope.spawn(||
+        {
+            let mut meshes = Vec::new();
+            loop
+            {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= num_chunks { break; }
+                let (cells_min, cells_max) = chunk(i);
+                let cell_counts = (cells_max.0 - cells_min.0, cells_max.1 - cells_min.1, cells_max.2 - cells_min.2);
+                let corner_counts = (cell_counts.0 + 1, cell_counts.1 + 1);
+                let corners = |layer: &mut [f32], zi| compute_corners(layer, zi, cells_min, origin, step_sizes, corner_counts, &sdf);
+                meshes.push((i, build_cells(corners, 0.0, origin, step_sizes, cells_min, cell_counts)));
+            }
+            meshes
+        })).collect();
+        handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+    });
+    meshes.sort_unstable_by_key(|(i, _)| *i);
+
+    //only edges lying in a brick face can be shared with other bricks
+    let mut shared = HashMap::new();
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for (i, (chunk_vertices, chunk_indices, chunk_edges)) in meshes
+    {
+        let (cells_min, cells_max) = chunk(i);
+        let on_face = |(x, y, z, axis): EdgeKey|
+            (axis != 0 && (x == cells_min.0 || x == cells_max.0))
+         || (axis != 1 && (y == cells_min.1 || y == cells_max.1))
+         || (axis != 2 && (z == cells_min.2 || z == cells_max.2));
+        let remap: Vec<u32> = chunk_vertices.into_iter().zip(chunk_edges).map(|(vertex, edge)|
+        {
+            let mut push = ||
+            {
+                vertices.push(vertex);
+                (vertices.len() - 1) as u32
+            };
+            if on_face(edge) { *shared.entry(edge).or_insert_with(push) } else { push() }
+        }).collect();
+        indices.extend(chunk_indices.into_iter().map(|index| remap[index as usize]));
+    }
+    (vertices, indices)
+}
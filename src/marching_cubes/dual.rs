@@ -0,0 +1,162 @@
+use super::*;
+
+const NO_VERTEX: u32 = u32::MAX;
+//pulls the qef solution towards the mass point where the normals are (close to) degenerate
+const QEF_REGULARIZATION: f32 = 0.05;
+
+//cube corners are indexed by bits: 1 ~ +x, 2 ~ +y, 4 ~ +z
+const EDGES: [(usize, usize); 12] =
+[
+    (0, 1), (2, 3), (4, 5), (6, 7),
+    (0, 2), (1, 3), (4, 6), (5, 7),
+    (0, 4), (1, 5), (2, 6), (3, 7)
+];
+
+//dual contouring: one vertex per cell (placed by a qef over the sdf gradients), one quad per edge
+//same output orientation as build, sharp features are kept
+//works in z slices: done ~ current z, doing ~ next z (corners and cells alike)
+pub fn build_dual(sdf: impl Fn(Vec3) -> f32, config: Config) -> (Vec<Vec3>, Vec<u32>)
+{
+    let Config { offset, radii, resolutions, .. } = config;
+    let corner_counts = (resolutions.0 + 1, resolutions.1 + 1);
+    let step_sizes = (2.0 * radii.0 / resolutions.0 as f32, 2.0 * radii.1 / resolutions.1 as f32, 2.0 * radii.2 / resolutions.2 as f32);
+    let origin = offset - radii;
+    let eps = step_sizes.0.min(step_sizes.1).min(step_sizes.2) * 0.01;
+
+    let mut done_corners = vec![f32::NAN; (corner_counts.0 * corner_counts.1) as usize];
+    let mut doing_corners = vec![f32::NAN; (corner_counts.0 * corner_counts.1) as usize];
+    let mut done_cells = vec![NO_VERTEX; (resolutions.0 * resolutions.1) as usize];
+    let mut doing_cells = vec![NO_VERTEX; (resolutions.0 * resolutions.1) as usize];
+
+    let mut vertices = vec![];
+    let mut indices = vec![];
+
+    let normal = |p: Vec3|
+    {
+        let gradient = gradient(&sdf, p, eps);
+        let norm = gradient.norm();
+        if norm > 0.0 { Some(gradient / norm) } else { None }
+    };
+
+    //front faces point towards negative values, same as build
+    fn quad(indices: &mut Vec<u32>, cells: [u32; 4], value_low: f32)
+    {
+        debug_assert!(cells.iter().all(|&cell| cell != NO_VERTEX));
+        let [a, b, c, d] = cells;
+        if value_low >= 0.0 { indices.extend_from_slice(&[a, b, c, a, c, d]); }
+        else { indices.extend_from_slice(&[a, c, b, a, d, c]); }
+    }
+
+    compute_corners(&mut done_corners, 0, (0, 0, 0), origin, step_sizes, corner_counts, &sdf);
+
+    for zi in 0..resolutions.2
+    {
+        compute_corners(&mut doing_corners, zi + 1, (0, 0, 0), origin, step_sizes, corner_counts, &sdf);
+
+        let cur_z = zi as f32 * step_sizes.2 + origin.2;
+        for yi in 0..resolutions.1
+        {
+            let cur_y = yi as f32 * step_sizes.1 + origin.1;
+            for xi in 0..resolutions.0
+            {
+                let cur_x = xi as f32 * step_sizes.0 + origin.0;
+                let cell_min = Vec3(cur_x, cur_y, cur_z);
+                let cell_max = cell_min + Vec3(step_sizes.0, step_sizes.1, step_sizes.2);
+
+                let corner_values: [f32; 8] = std::array::from_fn(|corner|
+                {
+                    let layer = if corner & 4 == 0 { &done_corners } else { &doing_corners };
+                    layer[((yi + ((corner as u32 >> 1) & 1)) * corner_counts.0 + xi + (corner as u32 & 1)) as usize]
+                });
+                let corner_position = |corner: usize| Vec3
+                (
+                    if corner & 1 == 0 { cell_min.0 } else { cell_max.0 },
+                    if corner & 2 == 0 { cell_min.1 } else { cell_max.1 },
+                    if corner & 4 == 0 { cell_min.2 } else { cell_max.2 }
+                );
+
+                let mut ata = Mat3::zero();
+                let mut atb = Vec3::zero();
+                let mut mass = Vec3::zero();
+                let mut count = 0;
+                for (corner_a, corner_b) in EDGES
+                {
+                    let (value_a, value_b) = (corner_values[corner_a], corner_values[corner_b]);
+                    if (value_a >= 0.0) == (value_b >= 0.0) { continue; }
+                    let factor_b = value_a / (value_a - value_b);
+                    let point = corner_position(corner_a) * (1.0 - factor_b) + corner_position(corner_b) * factor_b;
+                    mass += point;
+                    count += 1;
+                    if let Some(n) = normal(point)
+                    {
+                        ata.0 += n * n.0;
+                        ata.1 += n * n.1;
+                        ata.2 += n * n.2;
+                        atb += n * n.dot(point);
+                    }
+                }
+
+                let cell = &mut doing_cells[(yi * resolutions.0 + xi) as usize];
+                if count == 0
+                {
+                    *cell = NO_VERTEX;
+                    continue;
+                }
+                let mass_point = mass / count as f32;
+                let rhs = atb - ata * mass_point;
+                ata.0.0 += QEF_REGULARIZATION;
+                ata.1.1 += QEF_REGULARIZATION;
+                ata.2.2 += QEF_REGULARIZATION;
+                let vertex = (mass_point + ata.inverse() * rhs).component_max(cell_min).component_min(cell_max);
+                *cell = vertices.len() as u32;
+                vertices.push(vertex);
+            }
+        }
+
+        //z edges between the corner slices
+        for yi in 1..resolutions.1
+        {
+            for xi in 1..resolutions.0
+            {
+                let value_low = done_corners[(yi * corner_counts.0 + xi) as usize];
+                let value_high = doing_corners[(yi * corner_counts.0 + xi) as usize];
+                if (value_low >= 0.0) == (value_high >= 0.0) { continue; }
+                let cell = |x: u32, y: u32| doing_cells[(y * resolutions.0 + x) as usize];
+                quad(&mut indices, [cell(xi - 1, yi - 1), cell(xi, yi - 1), cell(xi, yi), cell(xi - 1, yi)], value_low);
+            }
+        }
+        //x and y edges of the done corner slice (needs the cells below and above)
+        if zi > 0
+        {
+            let done = |x: u32, y: u32| done_cells[(y * resolutions.0 + x) as usize];
+            let doing = |x: u32, y: u32| doing_cells[(y * resolutions.0 + x) as usize];
+            for yi in 0..corner_counts.1
+            {
+                for xi in 0..corner_counts.0
+                {
+                    let value_low = done_corners[(yi * corner_counts.0 + xi) as usize];
+                    if xi < resolutions.0 && yi > 0 && yi < resolutions.1
+                    {
+                        let value_high = done_corners[(yi * corner_counts.0 + xi + 1) as usize];
+                        if (value_low >= 0.0) != (value_high >= 0.0)
+                        {
+                            quad(&mut indices, [done(xi, yi - 1), done(xi, yi), doing(xi, yi), doing(xi, yi - 1)], value_low);
+                        }
+                    }
+                    if yi < resolutions.1 && xi > 0 && xi < resolutions.0
+                    {
+                        let value_high = done_corners[((yi + 1) * corner_counts.0 + xi) as usize];
+                        if (value_low >= 0.0) != (value_high >= 0.0)
+                        {
+                            quad(&mut indices, [done(xi - 1, yi), doing(xi - 1, yi), doing(xi, yi), done(xi, yi)], value_low);
+                        }
+                    }
+                }
+            }
+        }
+
+        std::mem::swap(&mut doing_corners, &mut done_corners);
+        std::mem::swap(&mut doing_cells, &mut done_cells);
+    }
+    (vertices, indices)
+}
@@ -1,26 +1,104 @@
 mod table;
+mod dual;
+mod chunked;
+
+pub use dual::build_dual;
+pub use chunked::build_chunked;
 
 use crate::math::*;
 use ahash::AHashMap as HashMap;
 use std::time::Instant;
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum Normals
+{
+    Gradient, //central differences of the sdf at each vertex
+    Faces //area weighted sum of the adjacent triangle normals
+}
+
 #[derive(Clone)]
 pub struct Config
 {
     pub offset: Vec3,
     pub radii: Vec3,
-    pub resolutions: (u32, u32, u32)
+    pub resolutions: (u32, u32, u32),
+    pub normals: Normals
+}
+
+fn gradient(sdf: &impl Fn(Vec3) -> f32, p: Vec3, eps: f32) -> Vec3
+{
+    Vec3
+    (
+        sdf(p + Vec3(eps, 0.0, 0.0)) - sdf(p - Vec3(eps, 0.0, 0.0)),
+        sdf(p + Vec3(0.0, eps, 0.0)) - sdf(p - Vec3(0.0, eps, 0.0)),
+        sdf(p + Vec3(0.0, 0.0, eps)) - sdf(p - Vec3(0.0, 0.0, eps))
+    ) / (2.0 * eps)
+}
+
+//global corner index (x, y, z) of the lower end + axis (0 ~ x, 1 ~ y, 2 ~ z)
+type EdgeKey = (u32, u32, u32, u8);
+
+fn compute_corners(vertices: &mut [f32], zi: u32, cells_min: (u32, u32, u32), origin: Vec3, step_sizes: (f32, f32, f32), corner_counts: (u32, u32), sdf: &impl Fn(Vec3) -> f32)
+{
+    let cur_z = zi as f32 * step_sizes.2 + origin.2;
+    for yi in 0..corner_counts.1
+    {
+        let cur_y = (cells_min.1 + yi) as f32 * step_sizes.1 + origin.1;
+        let vertices = &mut vertices[(yi  * corner_counts.0) as usize..];
+        for xi in 0..corner_counts.0
+        {
+            let cur_x = (cells_min.0 + xi) as f32 * step_sizes.0 + origin.0;
+            let value = sdf(Vec3(cur_x, cur_y, cur_z));
+            vertices[xi as usize] = value;
+        }
+    }
 }
 
-//works in z slices: done ~ current z, doing ~ next z
 pub fn build(sdf: impl Fn(Vec3) -> f32, config: Config) -> (Vec<Vec3>, Vec<u32>)
 {
     let before = Instant::now();
 
-    let Config { offset, radii, resolutions } = config;
-    let corner_counts = (resolutions.0 + 1, resolutions.1 + 1);
+    let Config { offset, radii, resolutions, .. } = config;
     let step_sizes = (2.0 * radii.0 / resolutions.0 as f32, 2.0 * radii.1 / resolutions.1 as f32, 2.0 * radii.2 / resolutions.2 as f32);
     let origin = offset - radii;
+    let corners = |layer: &mut [f32], zi| compute_corners(layer, zi, (0, 0, 0), origin, step_sizes, (resolutions.0 + 1, resolutions.1 + 1), &sdf);
+    let (vertices, indices, _) = build_cells(corners, 0.0, origin, step_sizes, (0, 0, 0), resolutions);
+
+    let after = std::time::Instant::now();
+    let _duration = after - before;
+    //println!("time: {} ms, vertices: {}", duration.as_millis(), vertices.len());
+    (vertices, indices)
+}
+
+//samples are laid out x first, then y, then z
+#[derive(Clone, Copy)]
+pub struct Grid<'a>
+{
+    pub values: &'a [f32],
+    pub dimensions: (u32, u32, u32), //number of samples per axis
+    pub origin: Vec3, //position of the first sample
+    pub spacing: Vec3
+}
+
+//values >= iso count as inside, like values >= 0.0 in build
+pub fn build_grid(grid: Grid, iso: f32) -> (Vec<Vec3>, Vec<u32>)
+{
+    let Grid { values, dimensions, origin, spacing } = grid;
+    assert!(dimensions.0 > 1 && dimensions.1 > 1 && dimensions.2 > 1, "marching_cubes::build_grid: less than 2 samples per axis");
+    let layer_size = dimensions.0 as usize * dimensions.1 as usize;
+    assert_eq!(values.len(), layer_size * dimensions.2 as usize, "marching_cubes::build_grid: value count does not match dimensions");
+    let corners = |layer: &mut [f32], zi: u32| layer.copy_from_slice(&values[zi as usize * layer_size..][..layer_size]);
+    let resolutions = (dimensions.0 - 1, dimensions.1 - 1, dimensions.2 - 1);
+    let (vertices, indices, _) = build_cells(corners, iso, origin, (spacing.0, spacing.1, spacing.2), (0, 0, 0), resolutions);
+    (vertices, indices)
+}
+
+//meshes the cells cells_min..cells_min + resolutions of the grid and returns the edge of every vertex
+//corners fills the corner values of one global z slice of the brick
+//works in z slices: done ~ current z, doing ~ next z
+fn build_cells(corners: impl Fn(&mut [f32], u32), iso: f32, origin: Vec3, step_sizes: (f32, f32, f32), cells_min: (u32, u32, u32), resolutions: (u32, u32, u32)) -> (Vec<Vec3>, Vec<u32>, Vec<EdgeKey>)
+{
+    let corner_counts = (resolutions.0 + 1, resolutions.1 + 1);
 
     let mut done_vertices = vec![f32::NAN; (corner_counts.0 * corner_counts.1) as usize];
     let mut doing_vertices = vec![f32::NAN; (corner_counts.0 * corner_counts.1) as usize];
@@ -30,37 +108,24 @@ pub fn build(sdf: impl Fn(Vec3) -> f32, config: Config) -> (Vec<Vec3>, Vec<u32>)
 
     let mut vertices = vec![];
     let mut indices = vec![];
+    let mut edges = vec![];
 
-    fn compute_corners(vertices: &mut [f32], cur_z: f32, origin: Vec3, step_sizes: (f32, f32, f32), corner_counts: (u32, u32), sdf: &impl Fn(Vec3) -> f32)
-    {
-        let cur_z = cur_z + origin.2;
-        for yi in 0..corner_counts.1
-        {
-            let cur_y = yi as f32 * step_sizes.1 + origin.1;
-            let vertices = &mut vertices[(yi  * corner_counts.0) as usize..];
-            for xi in 0..corner_counts.0
-            {
-                let cur_x = xi as f32 * step_sizes.0 + origin.0;
-                let value = sdf(Vec3(cur_x, cur_y, cur_z));
-                vertices[xi as usize] = value;
-            }
-        }
-    }
-    compute_corners(&mut done_vertices, 0.0, origin, step_sizes, corner_counts, &sdf);
+    corners(&mut done_vertices, cells_min.2);
     
     for zi in 0..resolutions.2
     {
-        compute_corners(&mut doing_vertices, (zi + 1) as f32 * step_sizes.2, origin, step_sizes, corner_counts, &sdf);
+        let (gx, gy, gz) = (cells_min.0, cells_min.1, cells_min.2 + zi);
+        corners(&mut doing_vertices, gz + 1);
 
-        let cur_z = zi as f32 * step_sizes.2 + origin.2;
+        let cur_z = gz as f32 * step_sizes.2 + origin.2;
         let next_z = cur_z + step_sizes.2;
         for yi in 0..resolutions.1
         {	
-            let cur_y = yi as f32 * step_sizes.1 + origin.1;
+            let cur_y = (gy + yi) as f32 * step_sizes.1 + origin.1;
             let next_y = cur_y + step_sizes.1;
             for xi in 0..resolutions.0
             {
-                let cur_x = xi as f32 * step_sizes.0 + origin.0;
+                let cur_x = (gx + xi) as f32 * step_sizes.0 + origin.0;
                 let next_x = cur_x + step_sizes.0;
 
                 let corners =
@@ -88,14 +153,14 @@ pub fn build(sdf: impl Fn(Vec3) -> f32, config: Config) -> (Vec<Vec3>, Vec<u32>)
                 ];
 
                 let table_code =
-                    ((if corner_values[0] >= 0.0 { 1 } else { 0 }) << 0)
-                  | ((if corner_values[1] >= 0.0 { 1 } else { 0 }) << 1)
-                  | ((if corner_values[2] >= 0.0 { 1 } else { 0 }) << 2)
-                  | ((if corner_values[3] >= 0.0 { 1 } else { 0 }) << 3)
-                  | ((if corner_values[4] >= 0.0 { 1 } else { 0 }) << 4)
-                  | ((if corner_values[5] >= 0.0 { 1 } else { 0 }) << 5)
-                  | ((if corner_values[6] >= 0.0 { 1 } else { 0 }) << 6)
-                  | ((if corner_values[7] >= 0.0 { 1 } else { 0 }) << 7);
+                    ((if corner_values[0] >= iso { 1 } else { 0 }) << 0)
+                  | ((if corner_values[1] >= iso { 1 } else { 0 }) << 1)
+                  | ((if corner_values[2] >= iso { 1 } else { 0 }) << 2)
+                  | ((if corner_values[3] >= iso { 1 } else { 0 }) << 3)
+                  | ((if corner_values[4] >= iso { 1 } else { 0 }) << 4)
+                  | ((if corner_values[5] >= iso { 1 } else { 0 }) << 5)
+                  | ((if corner_values[6] >= iso { 1 } else { 0 }) << 6)
+                  | ((if corner_values[7] >= iso { 1 } else { 0 }) << 7);
 
                 for &edge in table::TRIANGULATION[table_code].iter()
                 {
@@ -131,11 +196,29 @@ pub fn build(sdf: impl Fn(Vec3) -> f32, config: Config) -> (Vec<Vec3>, Vec<u32>)
                         let corner_value_a = corner_values[corner_index_a];
                         let corner_value_b = corner_values[corner_index_b];
                         
-                        let factor_b = corner_value_a / (corner_value_a - corner_value_b);
+                        let factor_b = (corner_value_a - iso) / (corner_value_a - corner_value_b);
                         let factor_a = 1.0 - factor_b;
                         let vertex = corner_a * factor_a + corner_b * factor_b;
+                        let (x, y, z) = (gx + xi, gy + yi, gz);
+                        let edge_key = match edge
+                        {
+                            7 => (x, y, z, 2),
+                            5 => (x + 1, y, z, 2),
+                            3 => (x, y + 1, z, 2),
+                            1 => (x + 1, y + 1, z, 2),
+                            11 => (x, y, z, 1),
+                            8 => (x, y, z + 1, 1),
+                            10 => (x + 1, y, z, 1),
+                            9 => (x + 1, y, z + 1, 1),
+                            6 => (x, y, z, 0),
+                            4 => (x, y, z + 1, 0),
+                            2 => (x, y + 1, z, 0),
+                            0 => (x, y + 1, z + 1, 0),
+                            _ => unreachable!()
+                        };
                         let index = vertices.len();
                         vertices.push(vertex);
+                        edges.push(edge_key);
                         index
                     });
                     indices.push(*index as u32);
@@ -147,8 +230,151 @@ pub fn build(sdf: impl Fn(Vec3) -> f32, config: Config) -> (Vec<Vec3>, Vec<u32>)
         doing_xy_edges.clear();
         doing_z_edges.clear();
     }
-    let after = std::time::Instant::now();
-    let _duration = after - before;
-    //println!("time: {} ms, vertices: {}", duration.as_millis(), vertices.len());
-    (vertices, indices)
+    (vertices, indices, edges)
+}
+
+//normals point towards negative sdf values, which is the front side of the triangles
+pub fn build_with_normals(sdf: impl Fn(Vec3) -> f32, config: Config) -> (Vec<Vec3>, Vec<Vec3>, Vec<u32>)
+{
+    let normal_mode = config.normals;
+    let eps = 0.01 * (2.0 * config.radii.0 / config.resolutions.0 as f32).min(2.0 * config.radii.1 / config.resolutions.1 as f32).min(2.0 * config.radii.2 / config.resolutions.2 as f32);
+    let (vertices, indices) = build(&sdf, config);
+    let normals = match normal_mode
+    {
+        Normals::Gradient => vertices.iter().map(|&vertex| -gradient(&sdf, vertex, eps).unit()).collect(),
+        Normals::Faces =>
+        {
+            let mut normals = vec![Vec3::zero(); vertices.len()];
+            for tri in indices.chunks_exact(3)
+            {
+                let [a, b, c] = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+                let normal = (vertices[b] - vertices[a]).cross(vertices[c] - vertices[a]);
+                normals[a] += normal;
+                normals[b] += normal;
+                normals[c] += normal;
+            }
+            normals.into_iter().map(|normal| normal.unit()).collect()
+        }
+    };
+    (vertices, normals, indices)
+}
+
+#[cfg(test)]
+mod tests //cargo test --features marching_cubes -- --nocapture
+{
+    use super::*;
+
+    fn volume(vertices: &[Vec3], indices: &[u32]) -> f32
+    {
+        indices.chunks_exact(3).map(|tri| vertices[tri[0] as usize].dot(vertices[tri[1] as usize].cross(vertices[tri[2] as usize])) / 6.0).sum()
+    }
+
+    fn closed(indices: &[u32]) -> bool
+    {
+        let mut edges = HashMap::new();
+        for tri in indices.chunks_exact(3)
+        {
+            for i in 0..3 { *edges.entry((tri[i], tri[(i + 1) % 3])).or_insert(0) += 1; }
+        }
+        edges.iter().all(|(&(a, b), &count)| count == 1 && edges.get(&(b, a)) == Some(&1))
+    }
+
+    #[test]
+    fn dual_cube()
+    {
+        let config = Config { offset: Vec3(0.1, 0.0, -0.1), radii: Vec3::one(), resolutions: (16, 16, 16), normals: Normals::Gradient };
+        let cube = |p: Vec3| (p - Vec3(0.1, 0.0, -0.1)).component_abs().max_component() - 0.55;
+        let (vertices, indices) = build(cube, config.clone());
+        let (dual_vertices, dual_indices) = build_dual(cube, config);
+        assert!(closed(&dual_indices));
+        let (volume, dual_volume) = (volume(&vertices, &indices), volume(&dual_vertices, &dual_indices));
+        println!("volume: {volume} (marching cubes), {dual_volume} (dual contouring), {} (exact)", 1.1f32.powi(3));
+        assert!(volume.signum() == dual_volume.signum());
+        assert!((dual_volume.abs() - 1.1f32.powi(3)).abs() < 0.01);
+    }
+
+    #[test]
+    fn chunked()
+    {
+        let config = Config { offset: Vec3::zero(), radii: Vec3(1.0, 1.2, 0.8), resolutions: (30, 25, 20), normals: Normals::Gradient };
+        let blob = |p: Vec3| p.norm() - 0.6 - 0.1 * (5.0 * p.0).sin() * (3.0 * p.1).cos();
+        let (vertices, indices) = build(blob, config.clone());
+        let (chunked_vertices, chunked_indices) = build_chunked(blob, config, (7, 8, 6), 4);
+        assert_eq!(vertices.len(), chunked_vertices.len());
+        assert_eq!(indices.len(), chunked_indices.len());
+        assert!(closed(&indices) && closed(&chunked_indices));
+        assert!((volume(&vertices, &indices) - volume(&chunked_vertices, &chunked_indices)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn grid()
+    {
+        let (dimensions, origin, spacing) = ((21, 17, 13), Vec3(-1.0, -0.8, -0.6), Vec3(0.1, 0.1, 0.1));
+        let density = |p: Vec3| 1.0 - p.norm();
+        let mut values = Vec::new();
+        for zi in 0..dimensions.2
+        {
+            for yi in 0..dimensions.1
+            {
+                for xi in 0..dimensions.0 { values.push(density(origin + Vec3(xi as f32, yi as f32, zi as f32).component_mul(spacing))); }
+            }
+        }
+        let (vertices, indices) = build_grid(Grid { values: &values, dimensions, origin, spacing }, 0.5);
+        let config = Config { offset: Vec3(0.0, 0.0, 0.0), radii: Vec3(1.0, 0.8, 0.6), resolutions: (20, 16, 12), normals: Normals::Gradient };
+        let (closure_vertices, closure_indices) = build(|p| density(p) - 0.5, config);
+        assert_eq!(indices, closure_indices);
+        assert!(closed(&indices));
+        assert!(vertices.iter().zip(closure_vertices.iter()).all(|(a, b)| (*a - *b).norm() < 1e-4));
+
+        //the sample count of large grids does not overflow u32
+        let err = std::panic::catch_unwind(|| build_grid(Grid { values: &[], dimensions: (1 << 16, 1 << 16, 2), origin, spacing }, 0.5)).unwrap_err();
+        assert!(err.downcast_ref::<String>().is_some_and(|message| message.contains("value count does not match")));
+    }
+
+    #[test]
+    fn normals()
+    {
+        let sphere = |p: Vec3| p.norm() - 0.7;
+        for normals in [Normals::Gradient, Normals::Faces]
+        {
+            let config = Config { offset: Vec3::zero(), radii: Vec3::one(), resolutions: (24, 24, 24), normals };
+            let (vertices, normals, indices) = build_with_normals(sphere, config);
+            assert_eq!(vertices.len(), normals.len());
+            for tri in indices.chunks_exact(3)
+            {
+                let [a, b, c] = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+                let face = (vertices[b] - vertices[a]).cross(vertices[c] - vertices[a]);
+                assert!(face.dot(normals[a]) > 0.0 && face.dot(normals[b]) > 0.0 && face.dot(normals[c]) > 0.0);
+            }
+            let error = vertices.iter().zip(normals.iter()).map(|(vertex, normal)| (vertex.unit() + *normal).norm()).fold(0.0, f32::max);
+            assert!(error < 0.1, "{error}");
+        }
+    }
+
+    #[test]
+    fn sdf_outward()
+    {
+        let config = Config { offset: Vec3::zero(), radii: Vec3::one(), resolutions: (24, 24, 24), normals: Normals::Gradient };
+        let (vertices, normals, indices) = build_with_normals(crate::math::sdf::negate(crate::math::sdf::sphere(0.7)), config);
+        assert!(vertices.iter().zip(&normals).all(|(vertex, normal)| (vertex.unit() - *normal).norm() < 0.1));
+        assert!(volume(&vertices, &indices) > 0.0);
+    }
+
+    #[test]
+    fn tangents()
+    {
+        let config = Config { offset: Vec3::zero(), radii: Vec3::one(), resolutions: (24, 24, 24), normals: Normals::Gradient };
+        let (vertices, normals, indices) = build_with_normals(|p: Vec3| p.norm() - 0.7, config);
+        let tex_coords: Vec<Vec2> = vertices.iter().map(|p| Vec2(p.0, p.1)).collect();
+        let (tangents, remap, _) = crate::math::tangents(&vertices, &normals, &tex_coords, &indices);
+        assert!(tangents.len() > vertices.len()); //the equator is a mirrored seam
+        for (&i, tangent) in remap.iter().zip(&tangents)
+        {
+            let (vertex, normal) = (vertices[i as usize], normals[i as usize]);
+            assert!((tangent.without_w().norm() - 1.0).abs() < 1e-4 && tangent.without_w().dot(normal).abs() < 1e-4);
+            //the normals point inwards, so the planar uvs have opposite handedness on the two caps
+            if vertex.2 > 0.65 { assert!(tangent.0 > 0.8 && tangent.3 == -1.0); }
+            if vertex.2 < -0.65 { assert!(tangent.0 > 0.8 && tangent.3 == 1.0); }
+        }
+    }
 }
@@ -7,7 +7,7 @@ use super::*;
 
 //All matrices are column major (=> ready for shaders)!
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "bytemuck", derive(Pod, Zeroable))]
 #[repr(C)]
@@ -109,7 +109,7 @@ impl Mat2
 	}
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "bytemuck", derive(Pod, Zeroable))]
 #[repr(C)]
@@ -295,7 +295,7 @@ impl Mat3
 	}
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "bytemuck", derive(Pod, Zeroable))]
 #[repr(C)]
@@ -481,6 +481,61 @@ impl Mat4
 		)
 	}
 
+	#[inline]
+	pub fn orthographic_opengl(left: f32, right: f32, bottom: f32, top: f32, z_near: f32, z_far: f32) -> Self
+	{
+		let (w, h, d) = (1.0 / (right - left), 1.0 / (top - bottom), 1.0 / (z_far - z_near));
+		Self
+		(
+			Vec4(2.0 * w, 0.0, 0.0, 0.0),
+			Vec4(0.0, 2.0 * h, 0.0, 0.0),
+			Vec4(0.0, 0.0, -2.0 * d, 0.0),
+			Vec4(-(right + left) * w, -(top + bottom) * h, -(z_far + z_near) * d, 1.0)
+		)
+	}
+
+	#[inline]
+	pub fn orthographic_vulkan(left: f32, right: f32, bottom: f32, top: f32, z_near: f32, z_far: f32) -> Self
+	{
+		let (w, h, d) = (1.0 / (right - left), 1.0 / (top - bottom), 1.0 / (z_far - z_near));
+		Self
+		(
+			Vec4(2.0 * w, 0.0, 0.0, 0.0),
+			Vec4(0.0, 2.0 * h, 0.0, 0.0),
+			Vec4(0.0, 0.0, d, 0.0),
+			Vec4(-(right + left) * w, -(top + bottom) * h, -z_near * d, 1.0)
+		)
+	}
+
+	#[inline]
+	pub fn orthographic_wgpu(left: f32, right: f32, bottom: f32, top: f32, z_near: f32, z_far: f32) -> Self
+	{
+		let (w, h, d) = (1.0 / (right - left), 1.0 / (top - bottom), 1.0 / (z_far - z_near));
+		Self
+		(
+			Vec4(2.0 * w, 0.0, 0.0, 0.0),
+			Vec4(0.0, 2.0 * h, 0.0, 0.0),
+			Vec4(0.0, 0.0, -d, 0.0),
+			Vec4(-(right + left) * w, -(top + bottom) * h, -z_near * d, 1.0)
+		)
+	}
+
+	//right handed view matrix: the camera looks along -z with +y up (use with the opengl and wgpu projections)
+	#[inline]
+	pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Self
+	{
+		let f = (target - eye).unit();
+		let s = f.cross(up).unit();
+		let u = s.cross(f);
+		Self
+		(
+			Vec4(s.0, u.0, -f.0, 0.0),
+			Vec4(s.1, u.1, -f.1, 0.0),
+			Vec4(s.2, u.2, -f.2, 0.0),
+			Vec4(-s.dot(eye), -u.dot(eye), f.dot(eye), 1.0)
+		)
+	}
+
 	#[inline]
 	pub const fn translation(Vec3(dx, dy, dz): Vec3) -> Self
 	{
@@ -561,6 +616,73 @@ impl Mat4
         )
 	}
 
+	#[inline]
+	pub fn inverse(self) -> Self
+	{
+		let (a, b, c, d) = (self.0, self.1, self.2, self.3);
+		//2x2 minors of the upper and lower row pairs
+		let s0 = a.0 * b.1 - b.0 * a.1;
+		let s1 = a.0 * c.1 - c.0 * a.1;
+		let s2 = a.0 * d.1 - d.0 * a.1;
+		let s3 = b.0 * c.1 - c.0 * b.1;
+		let s4 = b.0 * d.1 - d.0 * b.1;
+		let s5 = c.0 * d.1 - d.0 * c.1;
+		let c5 = c.2 * d.3 - d.2 * c.3;
+		let c4 = b.2 * d.3 - d.2 * b.3;
+		let c3 = b.2 * c.3 - c.2 * b.3;
+		let c2 = a.2 * d.3 - d.2 * a.3;
+		let c1 = a.2 * c.3 - c.2 * a.3;
+		let c0 = a.2 * b.3 - b.2 * a.3;
+		let norm = 1.0 / (s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0);
+		Self
+		(
+			Vec4
+			(
+				b.1 * c5 - c.1 * c4 + d.1 * c3,
+				-a.1 * c5 + c.1 * c2 - d.1 * c1,
+				a.1 * c4 - b.1 * c2 + d.1 * c0,
+				-a.1 * c3 + b.1 * c1 - c.1 * c0
+			) * norm, Vec4
+			(
+				-b.0 * c5 + c.0 * c4 - d.0 * c3,
+				a.0 * c5 - c.0 * c2 + d.0 * c1,
+				-a.0 * c4 + b.0 * c2 - d.0 * c0,
+				a.0 * c3 - b.0 * c1 + c.0 * c0
+			) * norm, Vec4
+			(
+				b.3 * s5 - c.3 * s4 + d.3 * s3,
+				-a.3 * s5 + c.3 * s2 - d.3 * s1,
+				a.3 * s4 - b.3 * s2 + d.3 * s0,
+				-a.3 * s3 + b.3 * s1 - c.3 * s0
+			) * norm, Vec4
+			(
+				-b.2 * s5 + c.2 * s4 - d.2 * s3,
+				a.2 * s5 - c.2 * s2 + d.2 * s1,
+				-a.2 * s4 + b.2 * s2 - d.2 * s0,
+				a.2 * s3 - b.2 * s1 + c.2 * s0
+			) * norm
+		)
+	}
+
+	#[inline]
+	pub fn det(self) -> f32
+	{
+		let (a, b, c, d) = (self.0, self.1, self.2, self.3);
+		let s0 = a.0 * b.1 - b.0 * a.1;
+		let s1 = a.0 * c.1 - c.0 * a.1;
+		let s2 = a.0 * d.1 - d.0 * a.1;
+		let s3 = b.0 * c.1 - c.0 * b.1;
+		let s4 = b.0 * d.1 - d.0 * b.1;
+		let s5 = c.0 * d.1 - d.0 * c.1;
+		let c5 = c.2 * d.3 - d.2 * c.3;
+		let c4 = b.2 * d.3 - d.2 * b.3;
+		let c3 = b.2 * c.3 - c.2 * b.3;
+		let c2 = a.2 * d.3 - d.2 * a.3;
+		let c1 = a.2 * c.3 - c.2 * a.3;
+		let c0 = a.2 * b.3 - b.2 * a.3;
+		s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0
+	}
+
 	#[inline]
 	pub fn transform(self, v: Vec3) -> Vec3
 	{
@@ -67,10 +67,15 @@ mod tests;
 mod vector;
 mod matrix;
 mod rotor;
+mod transform;
+mod tangents;
+pub mod sdf;
 
 pub use vector::*;
 pub use matrix::*;
 pub use rotor::*;
+pub use transform::*;
+pub use tangents::*;
 
 #[inline]
 pub fn smoothstep(x: f32, edge_l: f32, edge_r: f32) -> f32
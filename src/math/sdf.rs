@@ -0,0 +1,167 @@
+//signed distance functions (negative inside) as plain closures
+//marching_cubes puts the front faces towards negative values, pass negate(sdf) to get outward facing triangles and normals
+//z is the up axis of torus and cylinder
+
+use super::*;
+
+//     #####     PRIMITIVES     #####
+
+#[inline]
+pub fn sphere(radius: f32) -> impl Fn(Vec3) -> f32 + Clone
+{
+	move |p| p.norm() - radius
+}
+
+#[inline]
+pub fn cuboid(half_extents: Vec3) -> impl Fn(Vec3) -> f32 + Clone
+{
+	move |p|
+	{
+		let q = p.component_abs() - half_extents;
+		q.component_max(Vec3::zero()).norm() + q.max_component().min(0.0)
+	}
+}
+
+#[inline]
+pub fn rounded_cuboid(half_extents: Vec3, radius: f32) -> impl Fn(Vec3) -> f32 + Clone
+{
+	let cuboid = cuboid(half_extents - Vec3(radius, radius, radius));
+	move |p| cuboid(p) - radius
+}
+
+#[inline]
+pub fn torus(major_radius: f32, minor_radius: f32) -> impl Fn(Vec3) -> f32 + Clone
+{
+	move |p| Vec2(Vec2(p.0, p.1).norm() - major_radius, p.2).norm() - minor_radius
+}
+
+#[inline]
+pub fn capsule(a: Vec3, b: Vec3, radius: f32) -> impl Fn(Vec3) -> f32 + Clone
+{
+	let ab = b - a;
+	let ab_norm_sqr = ab.norm_sqr();
+	move |p|
+	{
+		let ap = p - a;
+		let t = (ap.dot(ab) / ab_norm_sqr).clamp(0.0, 1.0);
+		(ap - ab * t).norm() - radius
+	}
+}
+
+#[inline]
+pub fn cylinder(radius: f32, half_height: f32) -> impl Fn(Vec3) -> f32 + Clone
+{
+	move |p|
+	{
+		let d = Vec2(Vec2(p.0, p.1).norm() - radius, p.2.abs() - half_height);
+		d.max_component().min(0.0) + d.component_max(Vec2::zero()).norm()
+	}
+}
+
+//the unit normal points to the outside
+#[inline]
+pub fn plane(normal: Vec3, distance: f32) -> impl Fn(Vec3) -> f32 + Clone
+{
+	move |p| p.dot(normal) - distance
+}
+
+//     #####     CSG     #####
+
+#[inline]
+pub fn union(a: impl Fn(Vec3) -> f32 + Clone, b: impl Fn(Vec3) -> f32 + Clone) -> impl Fn(Vec3) -> f32 + Clone
+{
+	move |p| a(p).min(b(p))
+}
+
+#[inline]
+pub fn intersection(a: impl Fn(Vec3) -> f32 + Clone, b: impl Fn(Vec3) -> f32 + Clone) -> impl Fn(Vec3) -> f32 + Clone
+{
+	move |p| a(p).max(b(p))
+}
+
+//swaps inside and outside
+#[inline]
+pub fn negate(a: impl Fn(Vec3) -> f32 + Clone) -> impl Fn(Vec3) -> f32 + Clone
+{
+	move |p| -a(p)
+}
+
+//a without b
+#[inline]
+pub fn subtraction(a: impl Fn(Vec3) -> f32 + Clone, b: impl Fn(Vec3) -> f32 + Clone) -> impl Fn(Vec3) -> f32 + Clone
+{
+	move |p| a(p).max(-b(p))
+}
+
+//smooth minimum: blends over distances of about k
+#[inline]
+fn smooth_min(a: f32, b: f32, k: f32) -> f32
+{
+	let h = smoothstep(b - a, -k, k);
+	b + (a - b) * h - k * h * (1.0 - h)
+}
+
+#[inline]
+pub fn smooth_union(a: impl Fn(Vec3) -> f32 + Clone, b: impl Fn(Vec3) -> f32 + Clone, k: f32) -> impl Fn(Vec3) -> f32 + Clone
+{
+	move |p| smooth_min(a(p), b(p), k)
+}
+
+#[inline]
+pub fn smooth_intersection(a: impl Fn(Vec3) -> f32 + Clone, b: impl Fn(Vec3) -> f32 + Clone, k: f32) -> impl Fn(Vec3) -> f32 + Clone
+{
+	move |p| -smooth_min(-a(p), -b(p), k)
+}
+
+#[inline]
+pub fn smooth_subtraction(a: impl Fn(Vec3) -> f32 + Clone, b: impl Fn(Vec3) -> f32 + Clone, k: f32) -> impl Fn(Vec3) -> f32 + Clone
+{
+	move |p| -smooth_min(-a(p), b(p), k)
+}
+
+//     #####     DOMAIN     #####
+
+#[inline]
+pub fn translate(a: impl Fn(Vec3) -> f32 + Clone, offset: Vec3) -> impl Fn(Vec3) -> f32 + Clone
+{
+	move |p| a(p - offset)
+}
+
+#[inline]
+pub fn rotate(a: impl Fn(Vec3) -> f32 + Clone, rotor: Rotor) -> impl Fn(Vec3) -> f32 + Clone
+{
+	let inverse = rotor.inverse();
+	move |p| a(inverse.transform(p))
+}
+
+#[inline]
+pub fn scale(a: impl Fn(Vec3) -> f32 + Clone, scale: f32) -> impl Fn(Vec3) -> f32 + Clone
+{
+	move |p| a(p / scale) * scale
+}
+
+#[inline]
+pub fn transform(a: impl Fn(Vec3) -> f32 + Clone, transform: Transform) -> impl Fn(Vec3) -> f32 + Clone
+{
+	let inverse = transform.inverse();
+	move |p| a(inverse.transform(p)) * transform.scale
+}
+
+//only a bound of the distance for matrices that are not rigid
+#[inline]
+pub fn matrix(a: impl Fn(Vec3) -> f32 + Clone, matrix: Mat4) -> impl Fn(Vec3) -> f32 + Clone
+{
+	let inverse = matrix.inverse();
+	move |p| a(inverse.transform(p))
+}
+
+//infinite repetition, a should fit into one period around the origin
+#[inline]
+pub fn repeat(a: impl Fn(Vec3) -> f32 + Clone, period: Vec3) -> impl Fn(Vec3) -> f32 + Clone
+{
+	move |p|
+	{
+		let cell = Vec3((p.0 / period.0).round(), (p.1 / period.1).round(), (p.2 / period.2).round());
+		a(p - cell.component_mul(period))
+	}
+}
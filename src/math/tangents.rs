@@ -0,0 +1,82 @@
+use super::*;
+
+//per vertex tangents of an indexed triangle mesh, following MikkTSpace:
+//the face tangents are projected into the tangent plane of each vertex normal and weighted by the corner angle
+//w is the handedness, the bitangent is normal.cross(tangent.without_w()) * tangent.3
+//vertices whose faces disagree on the handedness (mirrored uvs) are split like in MikkTSpace, the copies are appended
+//returns the tangents, the source vertex of every returned vertex and the indices into the returned vertices
+//unlike MikkTSpace, vertices are not split where the tangents of one handedness differ by a sharp angle
+//vertices without usable uvs get an arbitrary tangent orthogonal to their normal
+pub fn tangents(positions: &[Vec3], normals: &[Vec3], tex_coords: &[Vec2], indices: &[u32]) -> (Vec<Vec4>, Vec<u32>, Vec<u32>)
+{
+	assert!(positions.len() == normals.len() && positions.len() == tex_coords.len(), "tangents: attribute counts differ");
+
+	//tangent sums and usage for positive and negative handedness
+	let mut sums = vec![[(Vec3::zero(), false); 2]; positions.len()];
+	//the handedness of every corner, None for degenerate uvs
+	let mut sides = vec![None; indices.len()];
+	for (triangle, triangle_sides) in indices.chunks_exact(3).zip(sides.chunks_exact_mut(3))
+	{
+		let (i0, i1, i2) = (triangle[0] as usize, triangle[1] as usize, triangle[2] as usize);
+		let (p0, p1, p2) = (positions[i0], positions[i1], positions[i2]);
+		let (uv0, uv1, uv2) = (tex_coords[i0], tex_coords[i1], tex_coords[i2]);
+		let (e1, e2) = (p1 - p0, p2 - p0);
+		let (duv1, duv2) = (uv1 - uv0, uv2 - uv0);
+		let r = duv1.0 * duv2.1 - duv2.0 * duv1.1;
+		if r.abs() < f32::MIN_POSITIVE { continue; }
+		let tangent = (e1 * duv2.1 - e2 * duv1.1) / r;
+		let bitangent = (e2 * duv1.0 - e1 * duv2.0) / r;
+
+		for ((i, a, b), side) in [(i0, p1 - p0, p2 - p0), (i1, p2 - p1, p0 - p1), (i2, p0 - p2, p1 - p2)].into_iter().zip(triangle_sides)
+		{
+			let normal = normals[i];
+			let handedness = (normal.cross(tangent).dot(bitangent) < 0.0) as usize;
+			*side = Some(handedness);
+			sums[i][handedness].1 = true;
+			let projected = tangent - normal * normal.dot(tangent);
+			if projected.norm_sqr() < f32::EPSILON * f32::EPSILON || a.norm_sqr() == 0.0 || b.norm_sqr() == 0.0 { continue; }
+			let angle = a.unit().dot(b.unit()).clamp(-1.0, 1.0).acos();
+			sums[i][handedness].0 += projected.unit() * angle;
+		}
+	}
+
+	let tangent = |sum: Vec3, normal: Vec3, handedness: usize|
+	{
+		let tangent = if sum.norm_sqr() > 0.0 { sum.unit() }
+		else
+		{
+			let axis = if normal.0.abs() < 0.9 { Vec3::e_x() } else { Vec3::e_y() };
+			(axis - normal * normal.dot(axis)).unit()
+		};
+		tangent.with_w(if handedness == 0 { 1.0 } else { -1.0 })
+	};
+	let mut tangents = Vec::with_capacity(positions.len());
+	let mut remap: Vec<u32> = (0..positions.len() as u32).collect();
+	//the handedness that keeps the vertex and the index of the copy with the other one
+	let mut kept = Vec::with_capacity(positions.len());
+	let mut split = Vec::new();
+	for (i, ([(positive, used_positive), (negative, used_negative)], &normal)) in sums.into_iter().zip(normals).enumerate()
+	{
+		let handedness = match (used_positive, used_negative)
+		{
+			(true, true) => (positive.norm_sqr() < negative.norm_sqr()) as usize,
+			(_, negative_only) => negative_only as usize
+		};
+		tangents.push(tangent([positive, negative][handedness], normal, handedness));
+		let copy = (used_positive && used_negative).then(||
+		{
+			split.push(tangent([positive, negative][1 - handedness], normal, 1 - handedness));
+			remap.push(i as u32);
+			(positions.len() + split.len() - 1) as u32
+		});
+		kept.push((handedness, copy));
+	}
+	tangents.append(&mut split);
+
+	let indices = indices.iter().zip(sides).map(|(&index, side)| match (kept[index as usize], side)
+	{
+		((handedness, Some(copy)), Some(side)) if side != handedness => copy,
+		_ => index
+	}).collect();
+	(tangents, remap, indices)
+}
@@ -28,6 +28,129 @@ fn mat3_inverse()
     perm(&mut components, 0);
 }
 
+#[test]
+fn mat4_inverse()
+{
+    let mat = Mat4::translation(Vec3(1.0, -2.0, 3.0)) * Mat4::rotation(Vec3(0.0, 0.6, 0.8), 0.7) * Mat4::scale(Vec3(2.0, 0.5, 3.0));
+    let mat = Mat4(mat.0, mat.1, mat.2, mat.3 + Vec4(0.1, 0.2, -0.3, 0.0));
+    assert!((mat.det() - mat.transpose().det()).abs() < 1e-4);
+    assert!((mat.det() * mat.inverse().det() - 1.0).abs() < 1e-4);
+    for prod in [mat * mat.inverse(), mat.inverse() * mat]
+    {
+        for (a, b) in prod.to_array().iter().zip(Mat4::identity().to_array().iter())
+        {
+            assert!((a - b).abs() < 1e-5, "{prod}");
+        }
+    }
+    let proj = Mat4::perspective_vulkan(1.5, 1.0, 0.1, 100.0);
+    for (a, b) in proj.inverse().to_array().iter().zip(Mat4::perspective_vulkan_inverse(1.5, 1.0, 0.1, 100.0).to_array().iter())
+    {
+        assert!((a - b).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn mat4_camera()
+{
+    let eye = Vec3(1.0, 2.0, 3.0);
+    let target = Vec3(-2.0, 0.5, 1.0);
+    let view = Mat4::look_at(eye, target, Vec3::e_z());
+    assert!(view.transform(eye).norm() < 1e-5);
+    let local = view.transform(target);
+    assert!(local.0.abs() < 1e-5 && local.1.abs() < 1e-5 && (local.2 + (target - eye).norm()).abs() < 1e-5);
+
+    let check = |proj: Mat4, z: f32, depth: (f32, f32)|
+    {
+        let near = proj.transform(Vec3(-4.0, -1.0, z * 0.5));
+        let far = proj.transform(Vec3(2.0, 3.0, z * 10.0));
+        for (a, b) in [(near.0, -1.0), (near.1, -1.0), (near.2, depth.0), (far.0, 1.0), (far.1, 1.0), (far.2, depth.1)]
+        {
+            assert!((a - b).abs() < 1e-5);
+        }
+    };
+    check(Mat4::orthographic_opengl(-4.0, 2.0, -1.0, 3.0, 0.5, 10.0), -1.0, (-1.0, 1.0));
+    check(Mat4::orthographic_vulkan(-4.0, 2.0, -1.0, 3.0, 0.5, 10.0), 1.0, (0.0, 1.0));
+    check(Mat4::orthographic_wgpu(-4.0, 2.0, -1.0, 3.0, 0.5, 10.0), -1.0, (0.0, 1.0));
+}
+
+#[test]
+fn transform()
+{
+    let a = Transform::new(Vec3(1.0, -2.0, 0.5), Rotor::from_unit_axis(Vec3(0.0, 0.6, 0.8), 0.9), 2.0);
+    let b = Transform::new(Vec3(-0.3, 4.0, 1.5), Rotor::from_unit_axis(Vec3::e_x(), -0.4), 0.25);
+    let p = Vec3(0.7, -1.1, 2.3);
+    let close = |u: Vec3, v: Vec3| assert!((u - v).norm() < 1e-4, "{u} != {v}");
+    close(a.transform(p), a.to_mat4().transform(p));
+    close((a * b).transform(p), a.transform(b.transform(p)));
+    close((a * b).transform(p), (a.to_mat4() * b.to_mat4()).transform(p));
+    close(a.inverse().transform(a.transform(p)), p);
+    close((a.to_mat4() * p.with_w0()).without_w(), a.transform_direction(p));
+    let slerp = TransformSlerp::new(a, b);
+    close(slerp.get(0.0).transform(p), a.transform(p));
+    close(slerp.get(1.0).transform(p), b.transform(p));
+}
+
+#[test]
+fn sdf()
+{
+    use sdf::*;
+    let close = |a: f32, b: f32| assert!((a - b).abs() < 1e-5, "{a} != {b}");
+    close(sphere(2.0)(Vec3(0.0, 3.0, 0.0)), 1.0);
+    close(cuboid(Vec3(1.0, 2.0, 3.0))(Vec3(4.0, 6.0, 0.0)), 5.0);
+    close(cuboid(Vec3(1.0, 2.0, 3.0))(Vec3(0.5, 0.0, 0.0)), -0.5);
+    close(rounded_cuboid(Vec3::one(), 0.2)(Vec3(1.0, 0.0, 0.0)), 0.0);
+    close(torus(2.0, 0.5)(Vec3(0.0, 2.0, 0.5)), 0.0);
+    close(capsule(Vec3::zero(), Vec3(0.0, 0.0, 2.0), 0.5)(Vec3(1.0, 0.0, 3.0)), 2.0f32.sqrt() - 0.5);
+    close(cylinder(1.0, 2.0)(Vec3(0.0, 3.0, 0.0)), 2.0);
+    close(plane(Vec3::e_z(), 1.0)(Vec3(5.0, 5.0, 3.0)), 2.0);
+
+    let a = sphere(1.0);
+    let b = translate(sphere(1.0), Vec3(1.5, 0.0, 0.0));
+    let p = Vec3(0.75, 0.8, 0.0);
+    close(union(a.clone(), b.clone())(p), a(p).min(b(p)));
+    close(intersection(a.clone(), b.clone())(p), a(p).max(b(p)));
+    close(subtraction(a.clone(), b.clone())(Vec3(-2.0, 0.0, 0.0)), 1.0);
+    assert!(smooth_union(a.clone(), b.clone(), 0.3)(p) < union(a.clone(), b.clone())(p));
+    assert!(smooth_intersection(a.clone(), b.clone(), 0.3)(p) > intersection(a.clone(), b.clone())(p));
+    close(smooth_union(a.clone(), b.clone(), 0.3)(Vec3(-2.0, 0.0, 0.0)), 1.0);
+
+    let rotor = Rotor::from_unit_axis(Vec3::e_z(), std::f32::consts::FRAC_PI_2);
+    close(rotate(cuboid(Vec3(2.0, 1.0, 1.0)), rotor)(Vec3(0.0, 2.0, 0.0)), 0.0);
+    close(transform(sphere(1.0), Transform::new(Vec3::e_x(), rotor, 2.0))(Vec3(4.0, 0.0, 0.0)), 1.0);
+    close(matrix(sphere(1.0), Mat4::translation(Vec3::e_y()))(Vec3(0.0, 3.0, 0.0)), 1.0);
+    close(scale(sphere(1.0), 3.0)(Vec3(0.0, 0.0, 4.0)), 1.0);
+    close(repeat(sphere(1.0), Vec3(4.0, 4.0, 4.0))(Vec3(8.0, -4.0, 1.5)), 0.5);
+}
+
+#[test]
+fn tangents()
+{
+    let positions = [Vec3(0.0, 0.0, 0.0), Vec3(1.0, 0.0, 0.0), Vec3(1.0, 1.0, 0.0), Vec3(0.0, 1.0, 0.0), Vec3(2.0, 2.0, 0.0)];
+    let normals = [Vec3::e_z(); 5];
+    let indices = [0, 1, 2, 0, 2, 3];
+    let close = |u: Vec4, v: Vec4| assert!((u - v).norm() < 1e-5, "{u} != {v}");
+
+    let tex_coords: Vec<Vec2> = positions.iter().map(|p| Vec2(p.0, p.1)).collect();
+    let (tangents, remap, new_indices) = super::tangents(&positions, &normals, &tex_coords, &indices);
+    assert_eq!((remap, new_indices), ((0..5).collect(), indices.to_vec()));
+    for tangent in &tangents[..4] { close(*tangent, Vec4(1.0, 0.0, 0.0, 1.0)); }
+    //unused vertex
+    assert!(tangents[4].without_w().dot(normals[4]).abs() < 1e-5 && (tangents[4].without_w().norm() - 1.0).abs() < 1e-5);
+
+    //mirrored uvs flip the handedness
+    let tex_coords: Vec<Vec2> = positions.iter().map(|p| Vec2(1.0 - p.0, p.1)).collect();
+    let (tangents, _, _) = super::tangents(&positions, &normals, &tex_coords, &indices);
+    for tangent in &tangents[..4] { close(*tangent, Vec4(-1.0, 0.0, 0.0, -1.0)); }
+
+    //a mirrored seam through 0 and 2, the second triangle gets copies of both
+    let tex_coords = [Vec2(0.0, 0.0), Vec2(1.0, 0.0), Vec2(1.0, 1.0), Vec2(2.0, 1.0), Vec2(0.0, 0.0)];
+    let (tangents, remap, new_indices) = super::tangents(&positions, &normals, &tex_coords, &indices);
+    assert_eq!(remap, [0, 1, 2, 3, 4, 0, 2]);
+    assert_eq!(new_indices, [0, 1, 2, 5, 6, 3]);
+    for i in [0, 1, 2] { close(tangents[i], Vec4(1.0, 0.0, 0.0, 1.0)); }
+    for i in [3, 5, 6] { close(tangents[i], Vec4(-1.0, 0.0, 0.0, -1.0)); }
+}
+
 /*
 #[test]
 fn rotor_from_plane()
@@ -0,0 +1,147 @@
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+#[cfg(feature = "bytemuck")]
+use bytemuck::{Pod, Zeroable};
+
+use super::*;
+
+//scale is uniform so that products and inverses stay representable
+//applied in the order scale -> rotation -> translation
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "bytemuck", derive(Pod, Zeroable))]
+#[repr(C)]
+pub struct Transform
+{
+	pub rotation: Rotor,
+	pub translation: Vec3,
+	pub scale: f32
+}
+static_assertions::const_assert_eq!(std::mem::size_of::<Transform>(), 32);
+
+impl Default for Transform
+{
+	fn default() -> Self
+	{
+		Self::identity()
+	}
+}
+
+impl Mul<Self> for Transform
+{
+	type Output = Self;
+	#[inline]
+	fn mul(self, other: Self) -> Self
+	{
+		Self
+		{
+			rotation: self.rotation * other.rotation,
+			translation: self.transform(other.translation),
+			scale: self.scale * other.scale
+		}
+	}
+}
+
+impl_mul_assign!(Transform, Self);
+
+impl Transform
+{
+	#[inline]
+	pub const fn new(translation: Vec3, rotation: Rotor, scale: f32) -> Self
+	{
+		Self { rotation, translation, scale }
+	}
+
+	#[inline]
+	pub const fn identity() -> Self
+	{
+		Self { rotation: Rotor::identity(), translation: Vec3::zero(), scale: 1.0 }
+	}
+
+	#[inline]
+	pub const fn from_translation(translation: Vec3) -> Self
+	{
+		Self { rotation: Rotor::identity(), translation, scale: 1.0 }
+	}
+
+	#[inline]
+	pub const fn from_rotation(rotation: Rotor) -> Self
+	{
+		Self { rotation, translation: Vec3::zero(), scale: 1.0 }
+	}
+
+	#[inline]
+	pub const fn from_scale(scale: f32) -> Self
+	{
+		Self { rotation: Rotor::identity(), translation: Vec3::zero(), scale }
+	}
+
+	#[inline]
+	pub fn inverse(self) -> Self
+	{
+		let rotation = self.rotation.inverse();
+		let scale = 1.0 / self.scale;
+		Self { rotation, translation: -rotation.transform(self.translation) * scale, scale }
+	}
+
+	#[inline]
+	pub fn transform(self, point: Vec3) -> Vec3
+	{
+		self.rotation.transform(point * self.scale) + self.translation
+	}
+
+	#[inline]
+	pub fn transform_direction(self, direction: Vec3) -> Vec3
+	{
+		self.rotation.transform(direction * self.scale)
+	}
+
+	#[inline]
+	pub fn to_mat4(self) -> Mat4
+	{
+		let Mat3(x, y, z) = self.rotation.to_mat3();
+		Mat4
+		(
+			(x * self.scale).with_w0(),
+			(y * self.scale).with_w0(),
+			(z * self.scale).with_w0(),
+			self.translation.with_w1()
+		)
+	}
+
+	#[inline]
+	pub fn fix(self) -> Self
+	{
+		Self { rotation: self.rotation.fix(), ..self }
+	}
+}
+
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TransformSlerp
+{
+	rotation: Slerp,
+	t1: Transform,
+	t2: Transform
+}
+
+impl TransformSlerp
+{
+	#[inline]
+	pub fn new(t1: Transform, t2: Transform) -> Self
+	{
+		Self { rotation: Slerp::new(t1.rotation, t2.rotation), t1, t2 }
+	}
+
+	#[inline]
+	pub fn get(self, t: f32) -> Transform
+	{
+		Transform
+		{
+			rotation: self.rotation.get(t),
+			translation: self.t1.translation * (1.0 - t) + self.t2.translation * t,
+			scale: self.t1.scale * (1.0 - t) + self.t2.scale * t
+		}
+	}
+}
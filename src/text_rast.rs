@@ -4,6 +4,31 @@ use serde::{Serialize, Deserialize};
 use ab_glyph::{FontRef, ScaleFont};
 use ahash::{AHashSet, AHashMap};
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error
+{
+	InvalidFont,
+	MissingGlyph(char),
+	TooWide(char),
+	TooHigh(char)
+}
+
+impl std::fmt::Display for Error
+{
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+	{
+		match self
+		{
+			Self::InvalidFont => write!(f, "Invalid font data."),
+			Self::MissingGlyph(ch) => write!(f, "Font does not contain \'{ch}\'."),
+			Self::TooWide(ch) => write!(f, "\'{ch}\' is too wide."),
+			Self::TooHigh(ch) => write!(f, "\'{ch}\' is too high.")
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
 #[derive(Clone)]
 pub struct Font<'a>
 {
@@ -14,7 +39,13 @@ impl<'a> Font<'a>
 {
 	pub fn new(data: &'a [u8]) -> Self
 	{
-		Font { font: FontRef::try_from_slice(data).unwrap() }
+		Self::try_new(data).unwrap_or_else(|err| panic!("Font::new: {err}"))
+	}
+
+	pub fn try_new(data: &'a [u8]) -> Result<Self, Error>
+	{
+		let font = FontRef::try_from_slice(data).map_err(|_| Error::InvalidFont)?;
+		Ok(Font { font })
 	}
 
 	pub fn digits() -> AHashSet<char>
@@ -103,6 +134,8 @@ struct Glyph
 pub struct Atlas
 {
 	glyphs: AHashMap<char, Glyph>,
+	#[cfg_attr(feature = "serde", serde(default))] //atlases serialized before kerning have none
+	kerning: AHashMap<char, AHashMap<char, f32>>, //left, right, nested because json keys are strings
 	ascent: f32,
 	space: f32,
 	default_glyph: Option<Glyph>
@@ -111,11 +144,16 @@ pub struct Atlas
 impl Atlas
 {
 	pub fn new<I: IntoIterator<Item = char>>(font: Font, chars: I, scale: f32, texture_size: u32, padding: u32) -> (Vec<Vec<u8>>, Self)
+	{
+		Self::try_new(font, chars, scale, texture_size, padding).unwrap_or_else(|err| panic!("Atlas::new: {err}"))
+	}
+
+	pub fn try_new<I: IntoIterator<Item = char>>(font: Font, chars: I, scale: f32, texture_size: u32, padding: u32) -> Result<(Vec<Vec<u8>>, Self), Error>
 	{
 		let mut builder = AtlasBuilder::new(font, [scale], texture_size, padding);
-		builder.add(0, chars);
+		builder.try_add(0, chars)?;
 		let (bitmap, [atlas]) = builder.finish();
-		(bitmap, atlas)
+		Ok((bitmap, atlas))
 	}
 
 	pub fn contains(&self, ch: char) -> bool
@@ -127,7 +165,7 @@ impl Atlas
 	{
 		struct Line<'a>
 		{
-			words: Vec<Vec<&'a Glyph>>,
+			words: Vec<Vec<(&'a Glyph, f32)>>, //glyph, advance including kerning
 			offset: f32,
 			space: f32
 		}
@@ -139,16 +177,12 @@ impl Atlas
 			let mut base_x = 0.0;
 			for word in block.split(' ')
 			{
-				let word: Vec<_> = word.chars().map(|ch| match self.glyphs.get(&ch)
+				let word: Vec<_> = word.chars().zip(word.chars().skip(1).map(Some).chain([None])).map(|(ch, next)|
 				{
-					Some(glyph) => glyph,
-					None => match &self.default_glyph
-					{
-						Some(glyph) => glyph,
-						None => panic!("Atlas::text: Atlas does not contain \'{}\'.", ch)
-					}
+					let glyph = self.glyph(ch);
+					(glyph, glyph.h_advance + next.map_or(0.0, |next| self.kern(ch, next)))
 				}).collect();
-				let word_length: f32 = word.iter().map(|glyph| glyph.h_advance).sum();
+				let word_length: f32 = word.iter().map(|(_, advance)| advance).sum();
 				if layout.auto_wrap && line.words.len() > 0 && base_x + self.space + word_length > layout.width
 				{
 					let margin = layout.width - base_x;
@@ -189,7 +223,7 @@ impl Atlas
 			let mut base_x = line.offset;
 			for word in &line.words
 			{
-				for glyph in word
+				for (glyph, advance) in word
 				{
 					index(base_index + 0);
 					index(base_index + 1);
@@ -208,7 +242,7 @@ impl Atlas
 					vertex((coords_x_min, coords_y_max, layer), (pos_x_min + base_x, pos_y_max + base_y));
 					vertex((coords_x_max, coords_y_max, layer), (pos_x_max + base_x, pos_y_max + base_y));
 					vertex((coords_x_max, coords_y_min, layer), (pos_x_max + base_x, pos_y_min + base_y));
-					base_x += glyph.h_advance;
+					base_x += advance;
 				}
 				base_x += line.space;
 			}
@@ -224,21 +258,10 @@ impl Atlas
 
 	pub fn width(&self, text: &str) -> f32
 	{
-		text.chars().map(|ch|
+		text.chars().zip(text.chars().skip(1).map(Some).chain([None])).map(|(ch, next)|
 		{
 			if ch == ' ' { self.space }
-			else
-			{
-				match self.glyphs.get(&ch)
-				{
-					Some(glyph) => glyph.h_advance,
-					None => match &self.default_glyph
-					{
-						Some(glyph) => glyph.h_advance,
-						None => panic!("Atlas::text: Atlas does not contain \'{}\'.", ch)
-					}
-				}
-			}
+			else { self.glyph(ch).h_advance + next.map_or(0.0, |next| self.kern(ch, next)) }
 		}).sum()
 	}
 
@@ -246,6 +269,24 @@ impl Atlas
 	{
 		self.default_glyph = glyph.as_ref().map(|ch| self.glyphs.get(ch).expect(&format!("Atlas::text: Atlas does not contain \'{}\'.", ch)).clone());
 	}
+
+	fn kern(&self, left: char, right: char) -> f32
+	{
+		self.kerning.get(&left).and_then(|right_kerning| right_kerning.get(&right)).copied().unwrap_or(0.0)
+	}
+
+	fn glyph(&self, ch: char) -> &Glyph
+	{
+		match self.glyphs.get(&ch)
+		{
+			Some(glyph) => glyph,
+			None => match &self.default_glyph
+			{
+				Some(glyph) => glyph,
+				None => panic!("Atlas::text: Atlas does not contain \'{}\'.", ch)
+			}
+		}
+	}
 }
 
 pub struct AtlasBuilder<'a, const N: usize>
@@ -288,7 +329,7 @@ impl<'a, const N: usize> AtlasBuilder<'a, N>
 			
 			let ascent = font_scaled.ascent() / scale;
 			let space = font_scaled.h_advance(font_scaled.glyph_id(' ')) / scale;
-			let atlas = Atlas { glyphs: AHashMap::new(), ascent, space, default_glyph: None };
+			let atlas = Atlas { glyphs: AHashMap::new(), kerning: AHashMap::new(), ascent, space, default_glyph: None };
 			(scale, atlas)
 		});
 		Self
@@ -305,6 +346,12 @@ impl<'a, const N: usize> AtlasBuilder<'a, N>
 	}
 
 	pub fn add<I: IntoIterator<Item = char>>(&mut self, i: usize, chars: I) -> bool
+	{
+		self.try_add(i, chars).unwrap_or_else(|err| panic!("AtlasBuilder::add: {err}"))
+	}
+
+	//on error, the builder is left unchanged
+	pub fn try_add<I: IntoIterator<Item = char>>(&mut self, i: usize, chars: I) -> Result<bool, Error>
 	{
 		use ab_glyph::Font;
 		let (scale, atlas) = &mut self.atlases[i];
@@ -313,16 +360,18 @@ impl<'a, const N: usize> AtlasBuilder<'a, N>
 			.into_iter()
 			.filter(|ch| *ch != ' ' && !atlas.contains(*ch))
 			.collect();
-		if chars.len() == 0 { return false; }
+		if chars.len() == 0 { return Ok(false); }
 		let mut chars: Vec<_> = chars
 			.into_iter()
 			.map(|ch|
 			{
-				let glyph = self.font.outline_glyph(font.scaled_glyph(ch)).expect(&format!("Atlas::new: Font does not contain \'{}\'.", ch));
+				let glyph = self.font.outline_glyph(font.scaled_glyph(ch)).ok_or(Error::MissingGlyph(ch))?;
 				let bounds = glyph.px_bounds();
-				(ch, glyph, bounds)
+				if bounds.width() as u32 >= self.texture_size { return Err(Error::TooWide(ch)); }
+				if bounds.height() as u32 >= self.texture_size { return Err(Error::TooHigh(ch)); }
+				Ok((ch, glyph, bounds))
 			})
-			.collect();
+			.collect::<Result<_, _>>()?;
 		 //improve packaging
 		chars.sort_by(|(_, _, b1), (_, _, b2)| (b1.height() as u32).cmp(&(b2.height() as u32)));
 		let mut i0 = 0;
@@ -332,6 +381,7 @@ impl<'a, const N: usize> AtlasBuilder<'a, N>
 		 && (chars[i0 + 1].2.height() as u32) < self.row_height
 		{ i0 = (i0 + 1) % chars.len(); }
 		chars.rotate_left(i0);
+		let new_chars: Vec<_> = chars.iter().map(|(ch, _, _)| *ch).collect();
 
 		let (x0, y0) = (&mut self.p0.0, &mut self.p0.1);
 		for (ch, glyph, bounds) in chars
@@ -340,7 +390,6 @@ impl<'a, const N: usize> AtlasBuilder<'a, N>
 			let height = bounds.height() as u32;
 			if *x0 + width >= self.texture_size
 			{
-				if *x0 == 0 { panic!("Atlas::new: \'{}\' is too wide.", ch); }
 				*x0 = 0;
 				*y0 += self.row_height + self.padding;
 				self.row_height = 0;
@@ -348,7 +397,6 @@ impl<'a, const N: usize> AtlasBuilder<'a, N>
             self.row_height = self.row_height.max(height);
 			if *y0 + height >= self.texture_size
 			{
-				if *y0 == 0 { panic!("Atlas::new: \'{}\' is too high.", ch); }
 				*x0 = 0;
 				*y0 = 0;
 				Self::new_layer(&mut self.layers, self.texture_size);
@@ -368,7 +416,20 @@ impl<'a, const N: usize> AtlasBuilder<'a, N>
 			atlas.glyphs.insert(ch, glyph);
 			*x0 += bounds.width() as u32 + self.padding;
 		}
-		true
+		//kerning pairs between the new glyphs and all glyphs of the atlas
+		//ab_glyph only reads the legacy kern table, fonts that keep their kerning in GPOS only are not kerned
+		for &new in &new_chars
+		{
+			for &old in atlas.glyphs.keys()
+			{
+				for (left, right) in [(new, old), (old, new)]
+				{
+					let kern = font.kern(font.glyph_id(left), font.glyph_id(right));
+					if kern != 0.0 { atlas.kerning.entry(left).or_default().insert(right, kern / *scale); }
+				}
+			}
+		}
+		Ok(true)
 	}
 
 	pub fn bitmap(&self) -> &Vec<Vec<u8>>
@@ -424,3 +485,31 @@ pub struct TextData
 	pub vertex_count: u32,
 	pub line_count: u32
 }
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn kerning()
+	{
+		//LatiniaKern.ttf is Latinia.ttf with a kern table for AV, VA, AT and To
+		let (_, atlas) = Atlas::new(Font::new(include_bytes!("../res/LatiniaKern.ttf")), ['A', 'V', 'T', 'o', 'x'], 32.0, 512, 2);
+		assert!(atlas.width("AV") < atlas.width("A") + atlas.width("V") - 0.05, "{} {}", atlas.width("AV"), atlas.width("A") + atlas.width("V"));
+		assert_eq!(atlas.width("Ax"), atlas.width("A") + atlas.width("x"));
+		assert_eq!(atlas.kern('A', 'V'), atlas.kern('V', 'A'));
+		let (_, plain) = Atlas::new(Font::new(include_bytes!("../res/Latinia.ttf")), ['A', 'V'], 32.0, 512, 2);
+		assert_eq!(plain.width("AV"), plain.width("A") + plain.width("V"));
+
+		#[cfg(feature = "serde")]
+		{
+			let json = serde_json::to_string(&atlas).unwrap();
+			assert_eq!(serde_json::from_str::<Atlas>(&json).unwrap().width("AV"), atlas.width("AV"));
+			let mut old = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+			old.as_object_mut().unwrap().remove("kerning");
+			let old: Atlas = serde_json::from_value(old).unwrap();
+			assert_eq!(old.width("AV"), atlas.width("A") + atlas.width("V"));
+		}
+	}
+}
@@ -1,19 +1,66 @@
+mod msdf;
+
 use easy_signed_distance_field as sdf;
 use ahash::AHashMap;
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error
+{
+    InvalidFont,
+    MissingGlyph(char),
+    TooWide(char),
+    TooHigh(char)
+}
+
+impl std::fmt::Display for Error
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self
+        {
+            Self::InvalidFont => write!(f, "Invalid font data."),
+            Self::MissingGlyph(ch) => write!(f, "Font does not contain \'{ch}\'."),
+            Self::TooWide(ch) => write!(f, "\'{ch}\' is too wide."),
+            Self::TooHigh(ch) => write!(f, "\'{ch}\' is too high.")
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 pub struct Font
 {
-    font: sdf::Font
+    font: sdf::Font,
+    data: Vec<u8> //sdf::Font drops the kerning table
 }
 
 impl Font
 {
     pub fn new(data: &[u8]) -> Self
     {
-        let font = sdf::Font::from_bytes(data, sdf::FontSettings::default()).unwrap();
-        Self { font }
+        Self::try_new(data).unwrap_or_else(|err| panic!("Font::new: {err}"))
+    }
+
+    //the font needs a space for the layout
+    pub fn try_new(data: &[u8]) -> Result<Self, Error>
+    {
+        ttf_parser::Face::parse(data, 0).map_err(|_| Error::InvalidFont)?;
+        let font = sdf::Font::from_bytes(data, sdf::FontSettings::default()).map_err(|_| Error::InvalidFont)?;
+        if font.metrics(' ', 1.0).is_none() { return Err(Error::MissingGlyph(' ')); }
+        Ok(Self { font, data: data.to_vec() })
+    }
+
+    //only the legacy kern table, many current fonts keep their kerning in GPOS only and are not kerned
+    fn kern_unscaled(face: &ttf_parser::Face, left: char, right: char) -> f32
+    {
+        let (Some(left), Some(right)) = (face.glyph_index(left), face.glyph_index(right)) else { return 0.0; };
+        face.tables().kern.into_iter()
+            .flat_map(|kern| kern.subtables)
+            .filter(|subtable| subtable.horizontal && !subtable.variable)
+            .find_map(|subtable| subtable.glyphs_kerning(left, right))
+            .map_or(0.0, f32::from)
     }
 
     pub fn digits() -> impl Iterator<Item = char>
@@ -76,6 +123,31 @@ impl Font
 	}
 }
 
+//msdf keeps sharp corners under magnification: sample with median(r, g, b) instead of r
+//range is the distance in texels from the outline to 0 or 1 (the outline is at 0.5)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Mode
+{
+	#[default]
+	Sdf, //r
+	Msdf { range: f32 }, //rgb
+	Mtsdf { range: f32 } //rgba, alpha holds the true sdf
+}
+
+impl Mode
+{
+	pub fn channels(self) -> u32
+	{
+		match self
+		{
+			Self::Sdf => 1,
+			Self::Msdf { .. } => 3,
+			Self::Mtsdf { .. } => 4
+		}
+	}
+}
+
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct Glyph
@@ -93,6 +165,10 @@ struct Glyph
 pub struct Atlas
 {
 	glyphs: AHashMap<char, Glyph>,
+	#[cfg_attr(feature = "serde", serde(default))] //atlases serialized before kerning have none
+	kerning: AHashMap<char, AHashMap<char, f32>>, //left, right, nested because json keys are strings
+	#[cfg_attr(feature = "serde", serde(default))] //atlases serialized before the modes are sdf
+	mode: Mode,
 	ascent: f32,
 	space: f32,
 	default_glyph: Option<Glyph>
@@ -102,10 +178,31 @@ impl Atlas
 {
 	pub fn new<I: IntoIterator<Item = char>>(font: Font, px: f32, chars: I, texture_size: u32, padding: u32) -> (Vec<Vec<u8>>, Self)
 	{
-		let mut builder = AtlasBuilder::new(font, px, texture_size, padding);
-		builder.add(chars);
+		Self::with_mode(font, px, chars, texture_size, padding, Mode::Sdf)
+	}
+
+	//the layers hold mode.channels() interleaved bytes per texel
+	pub fn with_mode<I: IntoIterator<Item = char>>(font: Font, px: f32, chars: I, texture_size: u32, padding: u32, mode: Mode) -> (Vec<Vec<u8>>, Self)
+	{
+		Self::try_with_mode(font, px, chars, texture_size, padding, mode).unwrap_or_else(|err| panic!("Atlas::new: {err}"))
+	}
+
+	pub fn try_new<I: IntoIterator<Item = char>>(font: Font, px: f32, chars: I, texture_size: u32, padding: u32) -> Result<(Vec<Vec<u8>>, Self), Error>
+	{
+		Self::try_with_mode(font, px, chars, texture_size, padding, Mode::Sdf)
+	}
+
+	pub fn try_with_mode<I: IntoIterator<Item = char>>(font: Font, px: f32, chars: I, texture_size: u32, padding: u32, mode: Mode) -> Result<(Vec<Vec<u8>>, Self), Error>
+	{
+		let mut builder = AtlasBuilder::with_mode(font, px, texture_size, padding, mode);
+		builder.try_add(chars)?;
 		let (bitmap, atlas) = builder.finish();
-		(bitmap, atlas)
+		Ok((bitmap, atlas))
+	}
+
+	pub fn mode(&self) -> Mode
+	{
+		self.mode
 	}
 
 	pub fn contains(&self, ch: char) -> bool
@@ -117,7 +214,7 @@ impl Atlas
 	{
 		struct Line<'a>
 		{
-			words: Vec<Vec<&'a Glyph>>,
+			words: Vec<Vec<(&'a Glyph, f32)>>, //glyph, advance including kerning
 			offset: f32,
 			space: f32
 		}
@@ -129,16 +226,12 @@ impl Atlas
 			let mut base_x = 0.0;
 			for word in block.split(' ')
 			{
-				let word: Vec<_> = word.chars().map(|ch| match self.glyphs.get(&ch)
+				let word: Vec<_> = word.chars().zip(word.chars().skip(1).map(Some).chain([None])).map(|(ch, next)|
 				{
-					Some(glyph) => glyph,
-					None => match &self.default_glyph
-					{
-						Some(glyph) => glyph,
-						None => panic!("Atlas::text: Atlas does not contain \'{}\'.", ch)
-					}
+					let glyph = self.glyph(ch);
+					(glyph, glyph.h_advance + next.map_or(0.0, |next| self.kern(ch, next)))
 				}).collect();
-				let word_length: f32 = word.iter().map(|glyph| glyph.h_advance).sum();
+				let word_length: f32 = word.iter().map(|(_, advance)| advance).sum();
 				if layout.auto_wrap && line.words.len() > 0 && base_x + self.space + word_length > layout.width
 				{
 					let margin = layout.width - base_x;
@@ -179,7 +272,7 @@ impl Atlas
 			let mut base_x = line.offset;
 			for word in &line.words
 			{
-				for glyph in word
+				for (glyph, advance) in word
 				{
 					index(base_index + 0);
 					index(base_index + 1);
@@ -198,7 +291,7 @@ impl Atlas
 					vertex((coords_x_min, coords_y_max, layer), (pos_x_min + base_x, pos_y_max + base_y));
 					vertex((coords_x_max, coords_y_max, layer), (pos_x_max + base_x, pos_y_max + base_y));
 					vertex((coords_x_max, coords_y_min, layer), (pos_x_max + base_x, pos_y_min + base_y));
-					base_x += glyph.h_advance;
+					base_x += advance;
 				}
 				base_x += line.space;
 			}
@@ -214,21 +307,10 @@ impl Atlas
 
 	pub fn width(&self, text: &str) -> f32
 	{
-		text.chars().map(|ch|
+		text.chars().zip(text.chars().skip(1).map(Some).chain([None])).map(|(ch, next)|
 		{
 			if ch == ' ' { self.space }
-			else
-			{
-				match self.glyphs.get(&ch)
-				{
-					Some(glyph) => glyph.h_advance,
-					None => match &self.default_glyph
-					{
-						Some(glyph) => glyph.h_advance,
-						None => panic!("Atlas::text: Atlas does not contain \'{}\'.", ch)
-					}
-				}
-			}
+			else { self.glyph(ch).h_advance + next.map_or(0.0, |next| self.kern(ch, next)) }
 		}).sum()
 	}
 
@@ -241,15 +323,9 @@ impl Atlas
 			let mut base_x = 0.0;
 			for word in block.split(' ')
 			{
-				let word_length: f32 = word.chars().map(|ch| match self.glyphs.get(&ch)
-				{
-					Some(glyph) => glyph,
-					None => match &self.default_glyph
-					{
-						Some(glyph) => glyph,
-						None => panic!("Atlas::text: Atlas does not contain \'{}\'.", ch)
-					}
-				}).map(|glyph| glyph.h_advance).sum();
+				let word_length: f32 = word.chars().zip(word.chars().skip(1).map(Some).chain([None]))
+					.map(|(ch, next)| self.glyph(ch).h_advance + next.map_or(0.0, |next| self.kern(ch, next)))
+					.sum();
 				if layout.auto_wrap && words > 0 && base_x + self.space + word_length > layout.width
 				{
 					lines += 1;
@@ -268,11 +344,30 @@ impl Atlas
 	{
 		self.default_glyph = glyph.as_ref().map(|ch| self.glyphs.get(ch).expect(&format!("Atlas::text: Atlas does not contain \'{}\'.", ch)).clone());
 	}
+
+	fn kern(&self, left: char, right: char) -> f32
+	{
+		self.kerning.get(&left).and_then(|right_kerning| right_kerning.get(&right)).copied().unwrap_or(0.0)
+	}
+
+	fn glyph(&self, ch: char) -> &Glyph
+	{
+		match self.glyphs.get(&ch)
+		{
+			Some(glyph) => glyph,
+			None => match &self.default_glyph
+			{
+				Some(glyph) => glyph,
+				None => panic!("Atlas::text: Atlas does not contain \'{}\'.", ch)
+			}
+		}
+	}
 }
 
 pub struct AtlasBuilder
 {
 	font: sdf::Font,
+	font_data: Vec<u8>,
 	texture_size: u32,
 	padding: u32,
 	p0: (u32, u32),
@@ -286,30 +381,36 @@ pub struct AtlasBuilder
 
 impl AtlasBuilder
 {
-	fn new_layer(layers: &mut Vec<Vec<u8>>, texture_size: u32)
+	fn new_layer(layers: &mut Vec<Vec<u8>>, texture_size: u32, mode: Mode)
 	{
 		let mut layer = Vec::new();
-		layer.resize((texture_size * texture_size) as usize, 0);
+		layer.resize((texture_size * texture_size * mode.channels()) as usize, 0);
 		layers.push(layer);
 	}
 
 	pub fn new(font: Font, px: f32, texture_size: u32, padding: u32) -> Self
+	{
+		Self::with_mode(font, px, texture_size, padding, Mode::Sdf)
+	}
+
+	pub fn with_mode(font: Font, px: f32, texture_size: u32, padding: u32, mode: Mode) -> Self
 	{
 		let mut layers = Vec::new();
-		Self::new_layer(&mut layers, texture_size);
+		Self::new_layer(&mut layers, texture_size, mode);
 		let coords_norm = 1.0 / texture_size as f32;
-        let font = font.font;
+        let Font { font, data: font_data } = font;
 		let (atlas, height) =
 		{
 			let metrics = font.horizontal_line_metrics(px);
 			let height = metrics.new_line_size;
 			let ascent = metrics.ascent / height;
 			let space = font.metrics(' ', px).unwrap().advance_width / height;
-			(Atlas { glyphs: AHashMap::new(), ascent, space, default_glyph: None }, height)
+			(Atlas { glyphs: AHashMap::new(), kerning: AHashMap::new(), mode, ascent, space, default_glyph: None }, height)
 		};
 		Self
 		{
 			font,
+			font_data,
 			texture_size,
 			padding,
 			p0: (0, 0),
@@ -323,20 +424,22 @@ impl AtlasBuilder
 	}
 
 	pub fn add<I: IntoIterator<Item = char>>(&mut self, chars: I) -> bool
+	{
+		self.try_add(chars).unwrap_or_else(|err| panic!("AtlasBuilder::add: {err}"))
+	}
+
+	//on error, the builder is left unchanged
+	pub fn try_add<I: IntoIterator<Item = char>>(&mut self, chars: I) -> Result<bool, Error>
 	{
 		let chars: ahash::AHashSet<_> = chars
 			.into_iter()
 			.filter(|ch| *ch != ' ' && !self.atlas.contains(*ch))
 			.collect();
-		if chars.len() == 0 { return false; }
+		if chars.len() == 0 { return Ok(false); }
 		let mut chars: Vec<_> = chars
 			.into_iter()
-			.map(|ch|
-			{
-				let metrics = self.font.metrics(ch, self.px).expect(&format!("AtlasBuild::add: Font does not contain \'{ch}\'."));
-				(ch, metrics)
-			})
-			.collect();
+			.map(|ch| Ok((ch, self.font.metrics(ch, self.px).ok_or(Error::MissingGlyph(ch))?)))
+			.collect::<Result<_, _>>()?;
 		 //improve packaging
 		chars.sort_by(|(_, m1), (_, m2)| m1.height.cmp(&m2.height));
 		let mut i0 = 0;
@@ -346,24 +449,50 @@ impl AtlasBuilder
 		 && (chars[i0 + 1].1.height as u32) < self.row_height
 		{ i0 = (i0 + 1) % chars.len(); }
 		chars.rotate_left(i0);
+		let new_chars: Vec<_> = chars.iter().map(|(ch, _)| *ch).collect();
 
-		let (x0, y0) = (&mut self.p0.0, &mut self.p0.1);
-		for (ch, metrics) in chars
+		let face = ttf_parser::Face::parse(&self.font_data, 0).ok();
+		//bitmap, its size, the glyph quad within it (in texels) and its position
+		let chars: Vec<_> = chars.into_iter().map(|(ch, metrics)|
 		{
-			let (sdf_width, sdf_height) = (metrics.width as u32, metrics.height as u32);
-			let sdf_padding = (sdf_width / 4).min(sdf_height / 4).max(1); //padding = size / 4 gives offset = size / 6 (weird stuff) 
-			let (ch_xoff, ch_yoff, ch_width, ch_height) =
+			let (bitmap, (sdf_width, sdf_height), quad, pos) = match self.atlas.mode
 			{
-				let (width, height, padding) = (sdf_width as f32, sdf_height as f32, sdf_padding as f32);
-				let (xoff, yoff) = ((width * padding) / (width + 2.0 * padding), (height * padding) / (height + 2.0 * padding));
-				let (width, height) = (width - 2.0 * xoff, height - 2.0 * yoff);
-				(xoff, yoff, width, height)
+				Mode::Sdf =>
+				{
+					let (sdf_width, sdf_height) = (metrics.width as u32, metrics.height as u32);
+					let sdf_padding = (sdf_width / 4).min(sdf_height / 4).max(1); //padding = size / 4 gives offset = size / 6 (weird stuff) 
+					let (ch_xoff, ch_yoff, ch_width, ch_height) =
+					{
+						let (width, height, padding) = (sdf_width as f32, sdf_height as f32, sdf_padding as f32);
+						let (xoff, yoff) = ((width * padding) / (width + 2.0 * padding), (height * padding) / (height + 2.0 * padding));
+						let (width, height) = (width - 2.0 * xoff, height - 2.0 * yoff);
+						(xoff, yoff, width, height)
+					};
+					let (xmin, ymax) = (metrics.bounds.xmin, -metrics.bounds.ymin);
+					let (xmax, ymin) = (xmin + metrics.bounds.width, ymax - metrics.bounds.height);
+					let sdf = self.font.sdf_generate(self.px, sdf_padding as i32, 2.0, ch).unwrap().1;
+					let bitmap = sdf.buffer.into_iter().map(|value| (value * 255.0) as u8).collect();
+					(bitmap, (sdf_width, sdf_height), ((ch_xoff - 0.5, ch_yoff - 0.5), (ch_yoff + ch_width + 0.5, ch_yoff + ch_height + 0.5)), ((xmin, ymin), (xmax, ymax)))
+				},
+				Mode::Msdf { range } | Mode::Mtsdf { range } =>
+				{
+					let face = face.as_ref().expect("AtlasBuilder::add: Font could not be parsed."); //checked by Font::try_new
+					let (shape, (left, top)) = msdf::Shape::new(face, ch, self.px / self.font.units_per_em(), range).ok_or(Error::MissingGlyph(ch))?;
+					let (width, height) = shape.size();
+					let bitmap = shape.generate(range, self.atlas.mode.channels() as usize);
+					(bitmap, (width, height), ((0.0, 0.0), (width as f32, height as f32)), ((left, -top), (left + width as f32, height as f32 - top)))
+				}
 			};
-			let (xmin, ymax) = (metrics.bounds.xmin, -metrics.bounds.ymin);
-			let (xmax, ymin) = (xmin + metrics.bounds.width, ymax - metrics.bounds.height);
+			if sdf_width >= self.texture_size { return Err(Error::TooWide(ch)); }
+			if sdf_height >= self.texture_size { return Err(Error::TooHigh(ch)); }
+			Ok((ch, metrics.advance_width, bitmap, (sdf_width, sdf_height), quad, pos))
+		}).collect::<Result<_, _>>()?;
+
+		let (x0, y0) = (&mut self.p0.0, &mut self.p0.1);
+		for (ch, advance_width, bitmap, (sdf_width, sdf_height), (quad_min, quad_max), (pos_min, pos_max)) in chars
+		{
 			if *x0 + sdf_width >= self.texture_size
 			{
-				if sdf_width >= self.texture_size { panic!("AtlasBuilder::add: \'{ch}\' is too wide."); }
 				*x0 = 0;
 				*y0 += self.row_height + self.padding;
 				self.row_height = 0;
@@ -371,34 +500,48 @@ impl AtlasBuilder
             self.row_height = self.row_height.max(sdf_height);
 			if *y0 + sdf_height >= self.texture_size
 			{
-				if sdf_height >= self.texture_size { panic!("AtlasBuilder::add: \'{ch}\' is too high."); }
 				*x0 = 0;
 				*y0 = 0;
-				Self::new_layer(&mut self.layers, self.texture_size);
+				Self::new_layer(&mut self.layers, self.texture_size, self.atlas.mode);
 			}
 			let layer = self.layers.len() - 1;
 			let buffer = &mut self.layers[layer];
-			let sdf = self.font.sdf_generate(self.px, sdf_padding as i32, 2.0, ch).unwrap().1;
-			for y in 0..sdf.height
+			let channels = self.atlas.mode.channels();
+			for y in 0..sdf_height
 			{
-				for x in 0..sdf.width
-				{
-					buffer[((y + *y0) * self.texture_size + x + *x0) as usize] = (sdf.buffer[(y * sdf.width + x) as usize] * 255.0) as u8;
-				}
+				let row = ((y + *y0) * self.texture_size + *x0) * channels;
+				let sdf_row = y * sdf_width * channels;
+				buffer[row as usize..(row + sdf_width * channels) as usize].copy_from_slice(&bitmap[sdf_row as usize..(sdf_row + sdf_width * channels) as usize]);
 			}
     		let glyph = Glyph
 			{
-				coords_min: ((*x0 as f32 + ch_xoff - 0.5) * self.coords_norm, (*y0 as f32 + ch_yoff - 0.5) * self.coords_norm),
-				coords_max: ((*x0 as f32 + ch_yoff + ch_width + 0.5) * self.coords_norm, (*y0 as f32 + ch_yoff + ch_height + 0.5) * self.coords_norm),
+				coords_min: ((*x0 as f32 + quad_min.0) * self.coords_norm, (*y0 as f32 + quad_min.1) * self.coords_norm),
+				coords_max: ((*x0 as f32 + quad_max.0) * self.coords_norm, (*y0 as f32 + quad_max.1) * self.coords_norm),
 				layer: layer as u32,
-				pos_min: (xmin / self.height, ymin / self.height),
-				pos_max: (xmax / self.height, ymax / self.height),
-				h_advance: metrics.advance_width / self.height
+				pos_min: (pos_min.0 / self.height, pos_min.1 / self.height),
+				pos_max: (pos_max.0 / self.height, pos_max.1 / self.height),
+				h_advance: advance_width / self.height
 			};
 			self.atlas.glyphs.insert(ch, glyph);
 			*x0 += sdf_width + self.padding;
 		}
-		true
+		//kerning pairs between the new glyphs and all glyphs of the atlas
+		if let Some(face) = &face
+		{
+			let kern_scale = self.px / self.font.units_per_em() / self.height;
+			for &new in &new_chars
+			{
+				for &old in self.atlas.glyphs.keys()
+				{
+					for (left, right) in [(new, old), (old, new)]
+					{
+						let kern = Font::kern_unscaled(face, left, right);
+						if kern != 0.0 { self.atlas.kerning.entry(left).or_default().insert(right, kern * kern_scale); }
+					}
+				}
+			}
+		}
+		Ok(true)
 	}
 
 	pub fn sdf(&self) -> &Vec<Vec<u8>>
@@ -418,7 +561,7 @@ impl AtlasBuilder
 
 	pub fn into_font(self) -> Font
 	{
-		Font { font: self.font }
+		Font { font: self.font, data: self.font_data }
 	}
 
 	pub fn finish(self) -> (Vec<Vec<u8>>, Atlas)
@@ -469,4 +612,62 @@ mod tests
 		let image = GrayImage::from_raw(1024, 1024, sdf.pop().unwrap()).unwrap();
 		image.save_with_format("all_letters.png", ImageFormat::Png).unwrap();
 	}
+
+	#[test]
+	fn msdf()
+	{
+		let (sdf, atlas) = Atlas::with_mode(Font::new(include_bytes!("../res/Latinia.ttf")), 32.0, Font::digits().chain(Font::all_letters()), 512, 2, Mode::Mtsdf { range: 4.0 });
+		assert_eq!(sdf.len(), 1);
+		assert_eq!(sdf[0].len(), 512 * 512 * 4);
+		//median and alpha at a relative position in the glyph quad
+		let texel = |ch: char, (u, v): (f32, f32)|
+		{
+			let glyph = &atlas.glyphs[&ch];
+			let x = ((glyph.coords_min.0 + (glyph.coords_max.0 - glyph.coords_min.0) * u) * 512.0) as usize;
+			let y = ((glyph.coords_min.1 + (glyph.coords_max.1 - glyph.coords_min.1) * v) * 512.0) as usize;
+			let [r, g, b, a] = sdf[0][(y * 512 + x) * 4..][..4] else { unreachable!() };
+			(r.min(g).max(r.max(g).min(b)), a)
+		};
+		for (ch, uv, inside) in [('I', (0.5, 0.5), true), ('O', (0.5, 0.5), false), ('A', (0.0, 0.0), false), ('l', (0.5, 0.5), true)]
+		{
+			let (median, alpha) = texel(ch, uv);
+			assert_eq!(median > 127, inside, "{ch}");
+			assert_eq!(alpha > 127, inside, "{ch}");
+		}
+	}
+
+	#[test]
+	fn errors()
+	{
+		assert!(matches!(Font::try_new(&[0, 1, 2, 3]), Err(Error::InvalidFont)));
+		let mut builder = AtlasBuilder::new(Font::new(include_bytes!("../res/Latinia.ttf")), 32.0, 24, 1);
+		assert_eq!(builder.try_add(['a', 'W']).unwrap_err(), Error::TooWide('W'));
+		assert_eq!(builder.try_add(['a', 'ẞ']).unwrap_err(), Error::MissingGlyph('ẞ'));
+		//nothing was added by the failed calls
+		assert!(!builder.atlas().contains('a'));
+		assert_eq!(builder.try_add(['.']), Ok(true));
+	}
+
+	#[test]
+	fn kerning()
+	{
+		//LatiniaKern.ttf is Latinia.ttf with a kern table for AV, VA, AT and To
+		let (_, atlas) = Atlas::new(Font::new(include_bytes!("../res/LatiniaKern.ttf")), 32.0, ['A', 'V', 'T', 'o', 'x'], 512, 2);
+		assert!(atlas.width("AV") < atlas.width("A") + atlas.width("V") - 0.05, "{} {}", atlas.width("AV"), atlas.width("A") + atlas.width("V"));
+		assert_eq!(atlas.width("Ax"), atlas.width("A") + atlas.width("x"));
+		let (_, plain) = Atlas::new(Font::new(include_bytes!("../res/Latinia.ttf")), 32.0, ['A', 'V'], 512, 2);
+		assert_eq!(plain.width("AV"), plain.width("A") + plain.width("V"));
+
+		#[cfg(feature = "serde")]
+		{
+			let json = serde_json::to_string(&atlas).unwrap();
+			assert_eq!(serde_json::from_str::<Atlas>(&json).unwrap().width("AV"), atlas.width("AV"));
+			let mut old = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+			old.as_object_mut().unwrap().remove("kerning");
+			old.as_object_mut().unwrap().remove("mode");
+			let old: Atlas = serde_json::from_value(old).unwrap();
+			assert_eq!(old.width("AV"), atlas.width("A") + atlas.width("V"));
+			assert_eq!(old.mode, Mode::Sdf);
+		}
+	}
 }
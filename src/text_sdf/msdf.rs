@@ -0,0 +1,488 @@
+//multi-channel signed distance fields (after Chlumsky, "Shape Decomposition for Multi-channel Distance Fields")
+//distances are measured in texels and positive inside, the inside is decided by the nonzero winding rule
+
+use crate::math::Vec2;
+
+const RED: u8 = 1;
+const GREEN: u8 = 2;
+const BLUE: u8 = 4;
+const CYAN: u8 = GREEN | BLUE;
+const WHITE: u8 = RED | GREEN | BLUE;
+//edges meet in a corner if the sine of their angle exceeds this (or they turn back)
+const CORNER_THRESHOLD: f32 = 0.14;
+//in texels: interpolated medians deviating more than this from the interpolated distance are checked against the exact distance
+const ARTIFACT_THRESHOLD: f32 = 0.25;
+const CURVE_SAMPLES: u32 = 8;
+const NEWTON_STEPS: u32 = 4;
+const FLATTEN_STEPS: u32 = 16;
+
+#[inline]
+fn cross(a: Vec2, b: Vec2) -> f32
+{
+	a.0 * b.1 - a.1 * b.0
+}
+
+#[inline]
+fn median(a: f32, b: f32, c: f32) -> f32
+{
+	a.min(b).max(a.max(b).min(c))
+}
+
+#[derive(Clone, Copy)]
+enum Segment
+{
+	Line(Vec2, Vec2),
+	Quad(Vec2, Vec2, Vec2),
+	Cubic(Vec2, Vec2, Vec2, Vec2)
+}
+
+impl Segment
+{
+	fn map(self, f: impl Fn(Vec2) -> Vec2) -> Self
+	{
+		match self
+		{
+			Self::Line(p0, p1) => Self::Line(f(p0), f(p1)),
+			Self::Quad(p0, p1, p2) => Self::Quad(f(p0), f(p1), f(p2)),
+			Self::Cubic(p0, p1, p2, p3) => Self::Cubic(f(p0), f(p1), f(p2), f(p3))
+		}
+	}
+
+	fn point(self, t: f32) -> Vec2
+	{
+		let s = 1.0 - t;
+		match self
+		{
+			Self::Line(p0, p1) => p0 * s + p1 * t,
+			Self::Quad(p0, p1, p2) => p0 * (s * s) + p1 * (2.0 * s * t) + p2 * (t * t),
+			Self::Cubic(p0, p1, p2, p3) => p0 * (s * s * s) + p1 * (3.0 * s * s * t) + p2 * (3.0 * s * t * t) + p3 * (t * t * t)
+		}
+	}
+
+	fn derivative(self, t: f32) -> Vec2
+	{
+		let s = 1.0 - t;
+		match self
+		{
+			Self::Line(p0, p1) => p1 - p0,
+			Self::Quad(p0, p1, p2) => (p1 - p0) * (2.0 * s) + (p2 - p1) * (2.0 * t),
+			Self::Cubic(p0, p1, p2, p3) => (p1 - p0) * (3.0 * s * s) + (p2 - p1) * (6.0 * s * t) + (p3 - p2) * (3.0 * t * t)
+		}
+	}
+
+	fn second_derivative(self, t: f32) -> Vec2
+	{
+		match self
+		{
+			Self::Line(..) => Vec2::zero(),
+			Self::Quad(p0, p1, p2) => (p2 - p1 * 2.0 + p0) * 2.0,
+			Self::Cubic(p0, p1, p2, p3) => (p2 - p1 * 2.0 + p0) * (6.0 * (1.0 - t)) + (p3 - p2 * 2.0 + p1) * (6.0 * t)
+		}
+	}
+
+	//tangents at the end points, skipping control points on top of them
+	fn direction_start(self) -> Vec2
+	{
+		match self
+		{
+			Self::Line(p0, p1) => p1 - p0,
+			Self::Quad(p0, p1, p2) => if p1 != p0 { p1 - p0 } else { p2 - p0 },
+			Self::Cubic(p0, p1, p2, p3) => if p1 != p0 { p1 - p0 } else if p2 != p0 { p2 - p0 } else { p3 - p0 }
+		}
+	}
+
+	fn direction_end(self) -> Vec2
+	{
+		match self
+		{
+			Self::Line(p0, p1) => p1 - p0,
+			Self::Quad(p0, p1, p2) => if p2 != p1 { p2 - p1 } else { p2 - p0 },
+			Self::Cubic(p0, p1, p2, p3) => if p3 != p2 { p3 - p2 } else if p3 != p1 { p3 - p1 } else { p3 - p0 }
+		}
+	}
+
+	fn reverse(self) -> Self
+	{
+		match self
+		{
+			Self::Line(p0, p1) => Self::Line(p1, p0),
+			Self::Quad(p0, p1, p2) => Self::Quad(p2, p1, p0),
+			Self::Cubic(p0, p1, p2, p3) => Self::Cubic(p3, p2, p1, p0)
+		}
+	}
+
+	//de casteljau
+	fn split(self, t: f32) -> (Self, Self)
+	{
+		let mix = |a: Vec2, b: Vec2| a * (1.0 - t) + b * t;
+		match self
+		{
+			Self::Line(p0, p1) =>
+			{
+				let p = mix(p0, p1);
+				(Self::Line(p0, p), Self::Line(p, p1))
+			},
+			Self::Quad(p0, p1, p2) =>
+			{
+				let (a, b) = (mix(p0, p1), mix(p1, p2));
+				let p = mix(a, b);
+				(Self::Quad(p0, a, p), Self::Quad(p, b, p2))
+			},
+			Self::Cubic(p0, p1, p2, p3) =>
+			{
+				let (a, b, c) = (mix(p0, p1), mix(p1, p2), mix(p2, p3));
+				let (d, e) = (mix(a, b), mix(b, c));
+				let p = mix(d, e);
+				(Self::Cubic(p0, a, d, p), Self::Cubic(p, e, c, p3))
+			}
+		}
+	}
+
+	fn split_in_thirds(self) -> [Self; 3]
+	{
+		let (first, rest) = self.split(1.0 / 3.0);
+		let (second, third) = rest.split(0.5);
+		[first, second, third]
+	}
+
+	//parameter of the closest point: exact for lines, sampled and refined by newton steps for curves
+	fn closest(self, p: Vec2) -> f32
+	{
+		let distance_sqr = |t: f32| (self.point(t) - p).norm_sqr();
+		match self
+		{
+			Self::Line(p0, p1) => ((p - p0).dot(p1 - p0) / (p1 - p0).norm_sqr()).clamp(0.0, 1.0),
+			_ =>
+			{
+				let sampled = (0..=CURVE_SAMPLES)
+					.map(|i| i as f32 / CURVE_SAMPLES as f32)
+					.min_by(|&t1, &t2| distance_sqr(t1).total_cmp(&distance_sqr(t2)))
+					.unwrap();
+				let mut t = sampled;
+				for _ in 0..NEWTON_STEPS
+				{
+					let (q, d1, d2) = (self.point(t) - p, self.derivative(t), self.second_derivative(t));
+					let slope = d1.norm_sqr() + q.dot(d2);
+					if slope <= 0.0 { break; }
+					t = (t - q.dot(d1) / slope).clamp(0.0, 1.0);
+				}
+				if distance_sqr(t) < distance_sqr(sampled) { t } else { sampled }
+			}
+		}
+	}
+
+	fn signed_distance(self, p: Vec2) -> (Distance, f32)
+	{
+		let t = self.closest(p);
+		let qp = p - self.point(t);
+		let direction = if t <= 0.0 { self.direction_start() } else if t >= 1.0 { self.direction_end() } else { self.derivative(t) };
+		let distance = qp.norm();
+		let sign = if cross(qp, direction) > 0.0 { 1.0 } else { -1.0 };
+		//ties at shared end points go to the edge pointing away from p the least
+		let dot = if (t > 0.0 && t < 1.0) || distance == 0.0 { 0.0 } else { (qp.dot(direction) / (distance * direction.norm())).abs() };
+		(Distance { distance: sign * distance, dot }, t)
+	}
+
+	//distance to the tangent line beyond the end points
+	fn pseudo_distance(self, distance: f32, p: Vec2, t: f32) -> f32
+	{
+		let extension = if t <= 0.0 { Some((self.point(0.0), self.direction_start().unit(), -1.0)) }
+			else if t >= 1.0 { Some((self.point(1.0), self.direction_end().unit(), 1.0)) }
+			else { None };
+		match extension
+		{
+			Some((q, direction, side)) if (p - q).dot(direction) * side > 0.0 =>
+			{
+				let pseudo_distance = cross(p - q, direction);
+				if pseudo_distance.abs() <= distance.abs() { pseudo_distance } else { distance }
+			},
+			_ => distance
+		}
+	}
+}
+
+#[derive(Clone, Copy)]
+struct Distance
+{
+	distance: f32,
+	dot: f32
+}
+
+impl Distance
+{
+	const FAR: Self = Self { distance: -f32::MAX, dot: 1.0 };
+
+	fn closer(self, other: Self) -> bool
+	{
+		let (a, b) = (self.distance.abs(), other.distance.abs());
+		a < b || (a == b && self.dot < other.dot)
+	}
+}
+
+struct Outline
+{
+	contours: Vec<Vec<Segment>>,
+	first: Vec2,
+	last: Vec2
+}
+
+impl Outline
+{
+	fn push(&mut self, segment: Segment, to: Vec2)
+	{
+		if to != self.last || !matches!(segment, Segment::Line(..)) { self.contours.last_mut().unwrap().push(segment); }
+		self.last = to;
+	}
+}
+
+impl ttf_parser::OutlineBuilder for Outline
+{
+	fn move_to(&mut self, x: f32, y: f32)
+	{
+		self.contours.push(Vec::new());
+		self.first = Vec2(x, y);
+		self.last = self.first;
+	}
+
+	fn line_to(&mut self, x: f32, y: f32)
+	{
+		self.push(Segment::Line(self.last, Vec2(x, y)), Vec2(x, y));
+	}
+
+	fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32)
+	{
+		self.push(Segment::Quad(self.last, Vec2(x1, y1), Vec2(x, y)), Vec2(x, y));
+	}
+
+	fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32)
+	{
+		self.push(Segment::Cubic(self.last, Vec2(x1, y1), Vec2(x2, y2), Vec2(x, y)), Vec2(x, y));
+	}
+
+	fn close(&mut self)
+	{
+		self.push(Segment::Line(self.last, self.first), self.first);
+	}
+}
+
+struct Edge
+{
+	segment: Segment,
+	color: u8
+}
+
+pub struct Shape
+{
+	contours: Vec<Vec<Edge>>,
+	size: (u32, u32)
+}
+
+impl Shape
+{
+	//the outline in texel coordinates (y down) with a margin of range texels
+	//also returns the top left corner of the bitmap in pixels (y up)
+	pub fn new(face: &ttf_parser::Face, ch: char, scale: f32, range: f32) -> Option<(Self, (f32, f32))>
+	{
+		let glyph = face.glyph_index(ch)?;
+		let mut outline = Outline { contours: Vec::new(), first: Vec2::zero(), last: Vec2::zero() };
+		let bounds = face.outline_glyph(glyph, &mut outline).unwrap_or(ttf_parser::Rect { x_min: 0, y_min: 0, x_max: 0, y_max: 0 });
+		let (left, top) = (bounds.x_min as f32 * scale - range, bounds.y_max as f32 * scale + range);
+		let size =
+		(
+			(bounds.width() as f32 * scale + 2.0 * range).ceil() as u32,
+			(bounds.height() as f32 * scale + 2.0 * range).ceil() as u32
+		);
+		let mut contours: Vec<Vec<_>> = outline.contours
+			.into_iter()
+			.filter(|segments| !segments.is_empty())
+			.map(|segments| segments.into_iter().map(|segment| segment.map(|p| Vec2(p.0 * scale - left, top - p.1 * scale))).collect())
+			.collect();
+		//truetype and cff outlines run in opposite directions, the outer contours decide the sign of the area
+		let area: f32 = contours.iter().flatten().flat_map(|segment: &Segment| flatten(*segment)).map(|(a, b)| cross(a, b)).sum();
+		if area > 0.0
+		{
+			for segments in &mut contours
+			{
+				segments.reverse();
+				for segment in segments { *segment = segment.reverse(); }
+			}
+		}
+		let mut color = WHITE;
+		let contours = contours.into_iter().map(|segments| color_contour(segments, &mut color)).collect();
+		Some((Self { contours, size }, (left, top)))
+	}
+
+	//exact signed distance
+	fn distance(&self, lines: &[(Vec2, Vec2)], p: Vec2) -> f32
+	{
+		let distance = self.contours.iter().flatten().map(|edge| edge.segment.signed_distance(p).0.distance.abs()).fold(f32::MAX, f32::min);
+		let winding: i32 = lines.iter()
+			.filter(|(a, b)| (a.1 <= p.1) != (b.1 <= p.1) && a.0 + (p.1 - a.1) / (b.1 - a.1) * (b.0 - a.0) < p.0)
+			.map(|(a, b)| if b.1 > a.1 { 1 } else { -1 })
+			.sum();
+		if winding != 0 { distance } else { -distance }
+	}
+
+	pub fn size(&self) -> (u32, u32)
+	{
+		self.size
+	}
+
+	//rgb(a) texels with 0.5 on the outline, the alpha channel holds the true distance
+	pub fn generate(&self, range: f32, channels: usize) -> Vec<u8>
+	{
+		let (width, height) = (self.size.0 as usize, self.size.1 as usize);
+		let lines: Vec<_> = self.contours.iter().flatten().flat_map(|edge| flatten(edge.segment)).collect();
+
+		let mut texels = Vec::with_capacity(width * height);
+		for y in 0..height
+		{
+			let py = y as f32 + 0.5;
+			let mut crossings: Vec<_> = lines.iter().filter_map(|&(a, b)|
+			{
+				if (a.1 <= py) == (b.1 <= py) { return None; }
+				let x = a.0 + (py - a.1) / (b.1 - a.1) * (b.0 - a.0);
+				Some((x, if b.1 > a.1 { 1 } else { -1 }))
+			}).collect();
+			crossings.sort_unstable_by(|(x1, _), (x2, _)| x1.total_cmp(x2));
+			let mut crossings = crossings.into_iter().peekable();
+			let mut winding = 0;
+			for x in 0..width
+			{
+				let p = Vec2(x as f32 + 0.5, py);
+				while let Some((_, delta)) = crossings.next_if(|(cx, _)| *cx < p.0) { winding += delta; }
+
+				let mut true_distance = Distance::FAR;
+				let mut closest = [(Distance::FAR, None); 3];
+				for edge in self.contours.iter().flatten()
+				{
+					let (distance, t) = edge.segment.signed_distance(p);
+					if distance.closer(true_distance) { true_distance = distance; }
+					for (channel, (closest_distance, closest_edge)) in closest.iter_mut().enumerate()
+					{
+						if edge.color & (1 << channel) != 0 && distance.closer(*closest_distance)
+						{
+							*closest_distance = distance;
+							*closest_edge = Some((edge.segment, t));
+						}
+					}
+				}
+				let [r, g, b] = closest.map(|(distance, edge)| edge.map_or(distance.distance, |(segment, t): (Segment, f32)| segment.pseudo_distance(distance.distance, p, t)));
+
+				//the median can still get the sign wrong where contours come close, the winding number decides
+				let inside = winding != 0;
+				let sign = if (median(r, g, b) > 0.0) == inside { 1.0 } else { -1.0 };
+				let alpha = if inside { true_distance.distance.abs() } else { -true_distance.distance.abs() };
+				texels.push([r * sign, g * sign, b * sign, alpha]);
+			}
+		}
+
+		//interpolating between texels whose channels change in different places can move the outline
+		//where the exact distance halfway between neighbours confirms that, both are flattened to their median
+		let mut artifacts = vec![false; width * height];
+		for i in 0..width * height
+		{
+			let (x, y) = (i % width, i / width);
+			for (dx, dy) in [(1, 0), (0, 1), (1, 1), (-1, 1)]
+			{
+				if (x == 0 && dx < 0) || (x + 1 == width && dx > 0) || (y + 1 == height && dy > 0) { continue; }
+				let j = (i + dy * width).wrapping_add_signed(dx);
+				let (a, b) = (texels[i], texels[j]);
+				let interpolated = median((a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0, (a[2] + b[2]) / 2.0);
+				let expected = (median(a[0], a[1], a[2]) + median(b[0], b[1], b[2])) / 2.0;
+				if (interpolated - expected).abs() < ARTIFACT_THRESHOLD { continue; }
+				let exact = self.distance(&lines, Vec2(x as f32 + 0.5 + dx as f32 / 2.0, y as f32 + 0.5 + dy as f32 / 2.0));
+				if (interpolated > 0.0) != (exact > 0.0) || (interpolated - exact).abs() > (expected - exact).abs() + ARTIFACT_THRESHOLD
+				{
+					artifacts[i] = true;
+					artifacts[j] = true;
+				}
+			}
+		}
+		for (texel, _) in texels.iter_mut().zip(artifacts).filter(|(_, artifact)| *artifact)
+		{
+			let m = median(texel[0], texel[1], texel[2]);
+			*texel = [m, m, m, texel[3]];
+		}
+
+		texels
+			.into_iter()
+			.flat_map(|texel| texel.into_iter().take(channels))
+			.map(|distance| ((0.5 + distance / (2.0 * range)).clamp(0.0, 1.0) * 255.0) as u8)
+			.collect()
+	}
+}
+
+fn flatten(segment: Segment) -> impl Iterator<Item = (Vec2, Vec2)>
+{
+	let steps = if let Segment::Line(..) = segment { 1 } else { FLATTEN_STEPS };
+	(0..steps).map(move |i| (segment.point(i as f32 / steps as f32), segment.point((i + 1) as f32 / steps as f32)))
+}
+
+fn switch_color(color: u8, banned: u8) -> u8
+{
+	let combined = color & banned;
+	if combined == RED || combined == GREEN || combined == BLUE { combined ^ WHITE }
+	else if color == 0 || color == WHITE { CYAN }
+	else
+	{
+		let shifted = color << 1;
+		(shifted | shifted >> 3) & WHITE
+	}
+}
+
+//-1, 0 or 1 for the first, middle and last third
+fn symmetrical_trichotomy(position: usize, n: usize) -> i32
+{
+	(3.0 + 2.875 * position as f32 / (n - 1) as f32 - 1.4375 + 0.5) as i32 - 3
+}
+
+//corners get two differently colored edges, so every channel has a sharp corner where at least two of them meet
+fn color_contour(segments: Vec<Segment>, color: &mut u8) -> Vec<Edge>
+{
+	let n = segments.len();
+	let corners: Vec<_> = (0..n).filter(|&i|
+	{
+		let (a, b) = (segments[(i + n - 1) % n].direction_end().unit(), segments[i].direction_start().unit());
+		a.dot(b) <= 0.0 || cross(a, b).abs() > CORNER_THRESHOLD
+	}).collect();
+	match corners[..]
+	{
+		[] => segments.into_iter().map(|segment| Edge { segment, color: WHITE }).collect(),
+		//teardrop: the contour is colored in thirds, splitting edges if there are too few
+		[corner] =>
+		{
+			let colors = [switch_color(*color, 0), WHITE, switch_color(switch_color(*color, 0), 0)];
+			*color = colors[2];
+			match n
+			{
+				1 => segments[0].split_in_thirds().into_iter().zip(colors).map(|(segment, color)| Edge { segment, color }).collect(),
+				2 => [segments[corner], segments[1 - corner]]
+					.into_iter()
+					.flat_map(Segment::split_in_thirds)
+					.zip([colors[0], colors[0], colors[1], colors[1], colors[2], colors[2]])
+					.map(|(segment, color)| Edge { segment, color })
+					.collect(),
+				_ => (0..n).map(|i| Edge { segment: segments[(corner + i) % n], color: colors[(1 + symmetrical_trichotomy(i, n)) as usize] }).collect()
+			}
+		},
+		_ =>
+		{
+			*color = switch_color(*color, 0);
+			let initial = *color;
+			let mut spline = 0;
+			let mut colors = vec![0; n];
+			for i in 0..n
+			{
+				let index = (corners[0] + i) % n;
+				if spline + 1 < corners.len() && corners[spline + 1] == index
+				{
+					spline += 1;
+					*color = switch_color(*color, if spline == corners.len() - 1 { initial } else { 0 });
+				}
+				colors[index] = *color;
+			}
+			segments.into_iter().zip(colors).map(|(segment, color)| Edge { segment, color }).collect()
+		}
+	}
+}
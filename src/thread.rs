@@ -1,8 +1,9 @@
-use std::{thread, any, marker::PhantomData, collections::VecDeque};
+use std::{thread, any, panic, marker::PhantomData, ops::Range, collections::VecDeque, sync::{Arc, Mutex, MutexGuard, Condvar, PoisonError}, future::Future, pin::Pin, task::{Context, Poll, Waker}};
 
 type Key = (usize, u32);
 type Res = Box<dyn any::Any + Send>;
 type Task = Box<dyn FnOnce() -> Res + Send>;
+type Dependent = Box<dyn FnOnce(Vec<Res>) -> Task + Send>;
 
 struct Worker
 {
@@ -14,45 +15,110 @@ struct Worker
 enum ResSlot
 {
     Free,
-    Pending,
-    Done(Option<Res>)
+    Pending(Option<Waker>), //the waker of an awaiting TaskKey
+    Done(Option<Result<Res, Res>>), //the error is the payload of a panic
+    Abandoned //cancelled while running, the result is dropped on arrival
+}
+
+//a task that is queued once all its dependencies are done
+struct Waiting
+{
+    key: Key,
+    priority: Priority,
+    dependencies: Vec<Key>,
+    task: Dependent
+}
+
+//shared between the pool, its workers and awaiting keys
+struct State
+{
+    slots: Vec<(u32, ResSlot)>,
+    free: Vec<usize>,
+    available: u32,
+    task: [VecDeque<(Key, Task)>; 3], //indexed by priority
+    waiting: Vec<Waiting>,
+    running: u32, //tasks taken by self driving workers
+    shutdown: bool
 }
 
-#[derive(PartialEq, Eq)]
+struct Shared
+{
+    state: Mutex<State>,
+    queued: Condvar, //self driving workers wait for tasks
+    finished: Condvar //blocking waits of self driving pools
+}
+
+//awaiting a key resolves to None if the key is invalid, e.g. cancelled, already queried or dropped with its pool before it ran
+//if the task panicked the panic is resumed in the awaiting task
 pub struct TaskKey<T>
 {
     key: Key,
-    _phantom: PhantomData<T>
+    shared: Arc<Shared>,
+    _phantom: PhantomData<fn() -> T>
+}
+
+//higher priorities are dispatched first, tasks of the same priority in spawn order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority
+{
+    Low,
+    Normal,
+    High
 }
 
 pub enum Query<T>
 {
     InvalidKey,
     Pending,
-    Done(T)
+    Done(T),
+    Panicked(Res) //the payload of the panic, the worker survives it
 }
 
 pub struct Pool
 {
     worker: Box<[Worker]>,
-    send: flume::Sender<(usize, Key, Res)>,
-    recv: flume::Receiver<(usize, Key, Res)>,
-    task: VecDeque<(Key, Task)>,
-    res: Vec<(u32, ResSlot)>,
-    free: Vec<usize>,
-    idle: u32,
-    available: u32
+    send: flume::Sender<usize>,
+    recv: flume::Receiver<usize>,
+    shared: Arc<Shared>,
+    self_driving: bool,
+    idle: u32
 }
 
 pub struct Shutdown(Vec<thread::JoinHandle<()>>);
 
+//tasks spawned on a scope may borrow from the caller of Pool::scope, which does not return before they are done
+pub struct Scope<'pool, 'env>
+{
+    pool: &'pool mut Pool,
+    keys: Vec<Key>,
+    _env: PhantomData<&'env mut &'env ()> //invariant like std::thread::Scope
+}
+
 impl Pool
 {
+    //tasks are handed to the workers by Pool::poll
     pub fn new(num_threads: usize) -> Self
+    {
+        Self::with_mode(num_threads, false)
+    }
+
+    //the workers take tasks from the shared queue themselves, Pool::poll is not needed
+    pub fn self_driving(num_threads: usize) -> Self
+    {
+        Self::with_mode(num_threads, true)
+    }
+
+    fn with_mode(num_threads: usize, self_driving: bool) -> Self
     {
         let (s2, r2) = flume::bounded(num_threads);
-        let worker = (0..num_threads).map(|i| Worker::new(i, s2.clone())).collect();
-        Self { worker, send: s2, recv: r2, task: VecDeque::new(), res: Vec::new(), free: Vec::new(), idle: num_threads as u32, available: 0 }
+        let shared = Arc::new(Shared
+        {
+            state: Mutex::new(State { slots: Vec::new(), free: Vec::new(), available: 0, task: Default::default(), waiting: Vec::new(), running: 0, shutdown: false }),
+            queued: Condvar::new(),
+            finished: Condvar::new()
+        });
+        let worker = (0..num_threads).map(|i| Worker::new(i, s2.clone(), shared.clone(), self_driving)).collect();
+        Self { worker, send: s2, recv: r2, shared, self_driving, idle: num_threads as u32 }
     }
 	
 	pub fn num_threads(&self) -> usize
@@ -60,85 +126,461 @@ impl Pool
 		self.worker.len()
 	}
 
+    //does nothing for self driving pools, unless they have no workers
     pub fn poll(&mut self)
     {
-        for (i, key, res) in self.recv.try_iter()
+        if self.worker.is_empty() { return self.run_inline(); }
+        if self.self_driving { return; }
+        let mut state = self.shared.lock();
+        let wakers = state.resolve_dependencies();
+        for i in self.recv.try_iter()
         {
             debug_assert!(!self.worker[i].free);
             self.worker[i].free = true;
             self.idle += 1;
-            let entry = &mut self.res[key.0];
-            if cfg!(debug_assertions)
-            {
-                if entry.0 != key.1 { panic!("Pool::poll: Generation mismatch (expected {}, found {}).", key.1, self.res[key.0].0); }
-                if !entry.1.pending() { panic!("Pool::poll: ResSlot not pending."); }
-            }
-            *entry = (key.1, ResSlot::Done(Some(res)));
-            self.available += 1;
         }
         for (i, worker) in self.worker.iter_mut().enumerate()
         {
             if worker.free //filtering the iterator does not work
             {
-                if let Some((key, task)) = self.task.pop_front()
+                if let Some((key, task)) = state.pop()
                 {
                     worker.free = false;
                     self.idle -= 1;
                     if let Err(flume::SendError(task)) = worker.submit.send((i, key, task))
                     { //replace broken worker and submit again
-                        *worker = Worker::new(i, self.send.clone());
+                        *worker = Worker::new(i, self.send.clone(), self.shared.clone(), false);
                         worker.free = false;
                         worker.submit.send(task).unwrap();
                     }
                 }
             }
         }
+        drop(state);
+        for waker in wakers { waker.wake(); }
     }
 
     pub fn spawn<T, F: FnOnce() -> T>(&mut self, task: F) -> TaskKey<T> where T: Send + 'static, F: Send + 'static
     {
-        if self.free.is_empty()
+        self.spawn_with_priority(Priority::Normal, task)
+    }
+
+    pub fn spawn_with_priority<T, F>(&mut self, priority: Priority, task: F) -> TaskKey<T> where T: Send + 'static, F: FnOnce() -> T + Send + 'static
+    {
+        let key = self.push(priority, Box::new(move || Box::new(task())));
+        TaskKey { key, shared: self.shared.clone(), _phantom: PhantomData }
+    }
+
+    fn push(&mut self, priority: Priority, task: Task) -> Key
+    {
+        let mut state = self.shared.lock();
+        let key = state.allocate();
+        state.task[priority as usize].push_back((key, task));
+        drop(state);
+        if self.self_driving { self.shared.queued.notify_one(); }
+        key
+    }
+
+    //queues the task once all keys are done and passes their results in the same order
+    //the results are consumed, querying or awaiting the keys afterwards gives an invalid key
+    //if a dependency panicked, its panic becomes the result of the task, if it is cancelled or queried before, the task is cancelled
+    //keys of another pool or passed more than once are invalid too, the results of the other keys stay available
+    pub fn spawn_after<T, U, F>(&mut self, keys: &[TaskKey<T>], task: F) -> TaskKey<U> where T: Send + 'static, U: Send + 'static, F: FnOnce(Vec<T>) -> U + Send + 'static
+    {
+        self.spawn_after_with_priority(Priority::Normal, keys, task)
+    }
+
+    pub fn spawn_after_with_priority<T, U, F>(&mut self, priority: Priority, keys: &[TaskKey<T>], task: F) -> TaskKey<U> where T: Send + 'static, U: Send + 'static, F: FnOnce(Vec<T>) -> U + Send + 'static
+    {
+        let dependent: Dependent = Box::new(move |results: Vec<Res>|
         {
-            self.free.push(self.res.len());
-            self.res.push((0, ResSlot::Free));
+            let results = results.into_iter().map(|res| *res.downcast::<T>().unwrap()).collect(); //should not panic if the generation logic is correct
+            Box::new(move || Box::new(task(results)))
+        });
+        let dependencies: Vec<Key> = keys.iter().map(|key| key.key).collect();
+        let mut unique = dependencies.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        let valid = unique.len() == dependencies.len() && keys.iter().all(|key| self.owns(key));
+        let mut state = self.shared.lock();
+        let key = state.allocate();
+        let wakers = if valid
+        {
+            state.waiting.push(Waiting { key, priority, dependencies, task: dependent });
+            state.resolve_dependencies()
         }
-        let i = self.free.pop().unwrap();
-        if cfg!(debug_assertions) && !self.res[i].1.free() { panic!("Pool::spawn: Free list error."); }
-        let key = (i, self.res[i].0);
-        self.res[i].1 = ResSlot::Pending;
-        self.task.push_back((key, Box::new(move || Box::new(task()))));
-        TaskKey { key, _phantom: PhantomData }
+        else { state.discard(key).into_iter().collect() };
+        drop(state);
+        self.shared.notify(wakers, self.self_driving);
+        TaskKey { key, shared: self.shared.clone(), _phantom: PhantomData }
     }
 
     pub fn idle_worker(&self) -> u32
     {
-        self.idle
+        if self.self_driving { self.worker.len() as u32 - self.shared.lock().running } else { self.idle }
     }
 
     pub fn available_results(&self) -> u32
     {
-        self.available
+        self.shared.lock().available
     }
 
     pub fn query<T: 'static>(&mut self, key: TaskKey<T>) -> Query<T>
     {
+        if !self.owns(&key) { return Query::InvalidKey; }
+        self.shared.lock().take(key.key)
+    }
+
+    //blocks until the task is done, never returns Query::Pending
+    pub fn wait<T: 'static>(&mut self, key: TaskKey<T>) -> Query<T>
+    {
+        if !self.owns(&key) { return Query::InvalidKey; }
+        loop
+        {
+            self.poll();
+            let shared = self.shared.clone();
+            let mut state = shared.lock();
+            match state.take(key.key)
+            {
+                Query::Pending => self.block(state),
+                query => return query
+            }
+        }
+    }
+
+    //blocks until all scoped tasks are done, even if f or polling the pool panics, e.g. in the waker of an awaiting key
+    //such a panic or that of a scoped task is resumed afterwards, the first one wins
+    pub fn scope<'env, R>(&mut self, f: impl FnOnce(&mut Scope<'_, 'env>) -> R) -> R
+    {
+        let mut scope = Scope { pool: self, keys: Vec::new(), _env: PhantomData };
+        let res = panic::catch_unwind(panic::AssertUnwindSafe(|| f(&mut scope)));
+        let Scope { pool, keys, .. } = scope;
+        let mut panicked = None;
+        //nothing may unwind out of here while the scoped tasks still borrow from the caller
+        loop
+        {
+            let done = panic::catch_unwind(panic::AssertUnwindSafe(||
+            {
+                pool.poll();
+                let shared = pool.shared.clone();
+                let state = shared.lock();
+                if keys.iter().all(|&key| state.done(key) == Some(true)) { return true; }
+                pool.block(state);
+                false
+            }));
+            match done
+            {
+                Ok(true) => break,
+                Ok(false) => (),
+                Err(payload) => { panicked.get_or_insert(payload); }
+            }
+        }
+        let mut state = pool.shared.lock();
+        let panicked = keys.into_iter().fold(panicked, |panicked, key| panicked.or(state.take_raw(key).err()));
+        drop(state);
+        match (res, panicked)
+        {
+            (Err(payload), _) | (Ok(_), Some(payload)) => panic::resume_unwind(payload),
+            (Ok(res), None) => res
+        }
+    }
+
+    //calls f with the index and the chunk for every chunk of chunk_size elements, the last one may be shorter
+    //consecutive chunks are grouped into a few tasks per worker, blocks until all are done
+    pub fn par_chunks_mut<T, F>(&mut self, data: &mut [T], chunk_size: usize, f: F) where T: Send, F: Fn(usize, &mut [T]) + Sync
+    {
+        assert!(chunk_size > 0, "Pool::par_chunks_mut: chunk size is 0");
+        let per_task = data.len().div_ceil(chunk_size).div_ceil(self.tasks_per_call()).max(1);
+        let f = &f;
+        self.scope(|s|
+        {
+            for (group, chunks) in data.chunks_mut(chunk_size * per_task).enumerate()
+            {
+                s.spawn(move || for (i, chunk) in chunks.chunks_mut(chunk_size).enumerate() { f(group * per_task + i, chunk); });
+            }
+        });
+    }
+
+    //calls f for every index of range, consecutive indices are grouped like in Pool::par_chunks_mut
+    pub fn par_for<F>(&mut self, range: Range<usize>, f: F) where F: Fn(usize) + Sync
+    {
+        let per_task = range.len().div_ceil(self.tasks_per_call()).max(1);
+        let f = &f;
+        self.scope(|s|
+        {
+            for start in range.clone().step_by(per_task)
+            {
+                let end = (start + per_task).min(range.end);
+                s.spawn(move || (start..end).for_each(f));
+            }
+        });
+    }
+
+    //keys of other pools index other slots
+    fn owns<T>(&self, key: &TaskKey<T>) -> bool
+    {
+        Arc::ptr_eq(&key.shared, &self.shared)
+    }
+
+    //a few tasks per worker balance uneven work
+    fn tasks_per_call(&self) -> usize
+    {
+        self.worker.len().max(1) * 4
+    }
+
+    //blocks until no task is queued, waiting or running, the results stay available
+    pub fn wait_all(&mut self)
+    {
+        loop
+        {
+            self.poll();
+            let shared = self.shared.clone();
+            let state = shared.lock();
+            let idle = if self.self_driving { state.running == 0 } else { self.idle as usize == self.worker.len() };
+            if idle && state.task.iter().all(VecDeque::is_empty) && state.waiting.is_empty() { return; }
+            self.block(state);
+        }
+    }
+
+    //pools without workers run all queued tasks on the calling thread when polled or waiting
+    fn run_inline(&mut self)
+    {
+        let mut state = self.shared.lock();
+        let mut wakers = state.resolve_dependencies();
+        while let Some((key, task)) = state.pop()
+        {
+            drop(state);
+            let res = panic::catch_unwind(panic::AssertUnwindSafe(task));
+            state = self.shared.lock();
+            wakers.extend(state.finish(key, res));
+            wakers.extend(state.resolve_dependencies());
+        }
+        drop(state);
+        self.shared.notify(wakers, self.self_driving);
+    }
+
+    //until a task finishes
+    fn block(&mut self, state: MutexGuard<State>)
+    {
+        //polling ran everything, so nothing could finish the tasks that are waited for
+        assert!(!self.worker.is_empty(), "Pool: waiting without workers for tasks that can never run");
+        if self.self_driving { drop(self.shared.finished.wait(state).unwrap_or_else(PoisonError::into_inner)); }
+        else
+        {
+            drop(state);
+            let i = self.recv.recv().unwrap(); //the pool holds a sender itself
+            debug_assert!(!self.worker[i].free);
+            self.worker[i].free = true;
+            self.idle += 1;
+        }
+    }
+
+    //drops a queued or waiting task and returns true, otherwise the result is dropped when it is or becomes available
+    //either way the key becomes invalid, use this to reclaim the results of keys that will never be queried
+    pub fn cancel<T>(&mut self, key: TaskKey<T>) -> bool
+    {
+        if !self.owns(&key) { return false; }
         let key = key.key;
-        let (generation, res) = &mut self.res[key.0]; //should not panic because self.res does never shrink
-        if *generation != key.1 { Query::InvalidKey }
-        else if let ResSlot::Done(res_box) = res
+        let mut state = self.shared.lock();
+        let state = &mut *state;
+        let (generation, res) = &mut state.slots[key.0];
+        if *generation != key.1 || matches!(res, ResSlot::Abandoned) { return false; }
+        let queued = state.task.iter_mut().any(|queue| queue.iter().position(|(queued, _)| *queued == key).and_then(|i| queue.remove(i)).is_some())
+            || state.waiting.iter().position(|waiting| waiting.key == key).map(|i| state.waiting.swap_remove(i)).is_some();
+        let mut wakers: Vec<Waker> = match std::mem::replace(res, ResSlot::Abandoned)
         {
-            let ret = *res_box.take().unwrap().downcast::<T>().unwrap(); //should not panic if the generation logic is correct
-            *generation = generation.wrapping_add(1);
-            *res = ResSlot::Free;
-            self.free.push(key.0);
-            self.available -= 1;
-            Query::Done(ret)
-        } else { Query::Pending }
+            ResSlot::Pending(waker) =>
+            {
+                if queued { state.release(key.0); }
+                waker
+            },
+            ResSlot::Done(_) =>
+            {
+                state.available -= 1;
+                state.release(key.0);
+                None
+            },
+            ResSlot::Free | ResSlot::Abandoned => unreachable!() //the generation matches
+        }.into_iter().collect();
+        wakers.extend(state.resolve_dependencies()); //tasks waiting for this one are cancelled
+        self.shared.notify(wakers, self.self_driving);
+        queued
+    }
+
+    pub fn shutdown(mut self) -> Shutdown
+    {
+        Shutdown(Vec::from(std::mem::take(&mut self.worker)).into_iter().map(|worker| worker.handle).collect())
+    }
+}
+
+impl<'env> Scope<'_, 'env>
+{
+    //scoped tasks are queued with Priority::High since the caller of Pool::scope blocks on them
+    pub fn spawn<F>(&mut self, task: F) where F: FnOnce() + Send + 'env
+    {
+        let task: Box<dyn FnOnce() -> Res + Send + 'env> = Box::new(move || { task(); Box::new(()) });
+        //SAFETY: Pool::scope does not return before the task is done, so nothing it borrows from 'env is dropped while it runs
+        let task: Task = unsafe { std::mem::transmute::<Box<dyn FnOnce() -> Res + Send + 'env>, Task>(task) };
+        let key = self.pool.push(Priority::High, task);
+        self.keys.push(key);
+    }
+}
+
+impl Drop for Pool
+{
+    //workers finish their current task and exit, queued and waiting tasks are dropped and their keys become invalid
+    fn drop(&mut self)
+    {
+        let mut state = self.shared.lock();
+        state.shutdown = true;
+        let queued: Vec<(Key, Task)> = state.task.iter_mut().flat_map(|queue| queue.drain(..)).collect();
+        let waiting: Vec<Waiting> = state.waiting.drain(..).collect();
+        let wakers: Vec<Waker> = queued.iter().map(|(key, _)| *key).chain(waiting.iter().map(|waiting| waiting.key)).filter_map(|key| state.discard(key)).collect();
+        drop(state);
+        //the tasks may own anything, they are not dropped under the lock
+        drop((queued, waiting));
+        self.shared.notify(wakers, true);
+    }
+}
+
+impl Shared
+{
+    //a panic while the lock is held must not take the pool and every awaiting key down with it
+    fn lock(&self) -> MutexGuard<'_, State>
+    {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
     }
 
-    pub fn shutdown(self) -> Shutdown
+    //to be called without holding the lock
+    fn notify(&self, wakers: Vec<Waker>, self_driving: bool)
     {
-        Shutdown(Vec::from(self.worker).into_iter().map(|worker| worker.handle).collect())
+        for waker in wakers { waker.wake(); }
+        if self_driving { self.queued.notify_all(); }
+        self.finished.notify_all();
+    }
+}
+
+impl State
+{
+    fn allocate(&mut self) -> Key
+    {
+        if self.free.is_empty()
+        {
+            self.free.push(self.slots.len());
+            self.slots.push((0, ResSlot::Free));
+        }
+        let i = self.free.pop().unwrap();
+        if cfg!(debug_assertions) && !self.slots[i].1.free() { panic!("Pool::spawn: Free list error."); }
+        self.slots[i].1 = ResSlot::Pending(None);
+        (i, self.slots[i].0)
+    }
+
+    //the next task by priority
+    fn pop(&mut self) -> Option<(Key, Task)>
+    {
+        self.task.iter_mut().rev().find_map(VecDeque::pop_front)
+    }
+
+    //queues waiting tasks whose dependencies are done, fails or cancels them if a dependency did
+    fn resolve_dependencies(&mut self) -> Vec<Waker>
+    {
+        let mut wakers = Vec::new();
+        let mut i = 0;
+        while i < self.waiting.len()
+        {
+            let waiting = &self.waiting[i];
+            let states: Vec<Option<bool>> = waiting.dependencies.iter().map(|&key| self.done(key)).collect();
+            if states.contains(&Some(false)) && !states.contains(&None) { i += 1; continue; }
+
+            let Waiting { key, priority, dependencies, task } = self.waiting.swap_remove(i);
+            if states.contains(&None)
+            {
+                //the results of the other dependencies stay available
+                wakers.extend(self.discard(key));
+                continue;
+            }
+            let res: Result<Vec<Res>, Res> = dependencies.into_iter().map(|dependency| self.take_raw(dependency)).collect();
+            match res
+            {
+                Ok(res) => self.task[priority as usize].push_back((key, task(res))),
+                Err(payload) => wakers.extend(self.finish(key, Err(payload)))
+            }
+        }
+        wakers
+    }
+
+    //None for invalid keys
+    fn done(&self, key: Key) -> Option<bool>
+    {
+        let (generation, res) = &self.slots[key.0]; //should not panic because the slots never shrink
+        if *generation != key.1 { return None; }
+        match res
+        {
+            ResSlot::Pending(_) => Some(false),
+            ResSlot::Done(_) => Some(true),
+            ResSlot::Free | ResSlot::Abandoned => None
+        }
+    }
+
+    //the key must be done
+    fn take_raw(&mut self, key: Key) -> Result<Res, Res>
+    {
+        let ResSlot::Done(res) = &mut self.slots[key.0].1 else { unreachable!() };
+        let res = res.take().unwrap();
+        self.release(key.0);
+        self.available -= 1;
+        res
+    }
+
+    fn take<T: 'static>(&mut self, key: Key) -> Query<T>
+    {
+        match self.done(key)
+        {
+            None => Query::InvalidKey,
+            Some(false) => Query::Pending,
+            Some(true) => match self.take_raw(key)
+            {
+                Ok(res) => Query::Done(*res.downcast::<T>().unwrap()), //should not panic if the generation logic is correct
+                Err(payload) => Query::Panicked(payload)
+            }
+        }
+    }
+
+    //releases a pending slot whose task will never run
+    fn discard(&mut self, key: Key) -> Option<Waker>
+    {
+        let ResSlot::Pending(waker) = std::mem::replace(&mut self.slots[key.0].1, ResSlot::Free) else { unreachable!() };
+        self.release(key.0);
+        waker
+    }
+
+    //stores the result of a finished task and returns the waker to notify
+    fn finish(&mut self, key: Key, res: Result<Res, Res>) -> Option<Waker>
+    {
+        let entry = &mut self.slots[key.0];
+        if cfg!(debug_assertions) && entry.0 != key.1 { panic!("Pool: Generation mismatch (expected {}, found {}).", key.1, entry.0); }
+        match std::mem::replace(&mut entry.1, ResSlot::Done(Some(res)))
+        {
+            ResSlot::Pending(waker) =>
+            {
+                self.available += 1;
+                waker
+            },
+            ResSlot::Abandoned =>
+            {
+                self.release(key.0);
+                None
+            },
+            _ => panic!("Pool: ResSlot not pending.")
+        }
+    }
+
+    fn release(&mut self, i: usize)
+    {
+        let (generation, res) = &mut self.slots[i];
+        *generation = generation.wrapping_add(1);
+        *res = ResSlot::Free;
+        self.free.push(i);
     }
 }
 
@@ -150,7 +592,8 @@ impl<T> Query<T>
         {
             Self::InvalidKey => panic!("unwrap Query::InvalidKey"),
             Self::Pending => panic!("unwrap Query::Pending"),
-            Self::Done(t) => t
+            Self::Done(t) => t,
+            Self::Panicked(payload) => panic::resume_unwind(payload)
         }
     }
 }
@@ -170,15 +613,38 @@ impl Shutdown
 
 impl Worker
 {
-    fn new(id: usize, send: flume::Sender<(usize, Key, Res)>) -> Self
+    fn new(id: usize, send: flume::Sender<usize>, shared: Arc<Shared>, self_driving: bool) -> Self
     {
         let (s1, r1) = flume::bounded::<(usize, Key, Task)>(1);
+        //a panicking task must not take the worker down, its slot would stay pending forever
+        let run = |task: Task| panic::catch_unwind(panic::AssertUnwindSafe(task));
         let handle = thread::Builder::new().name(format!("Worker {id}")).spawn(move ||
         {
-            for (i, key, task) in r1
+            if !self_driving
+            {
+                for (i, key, task) in r1
+                {
+                    let res = run(task);
+                    let waker = shared.lock().finish(key, res);
+                    if let Some(waker) = waker { waker.wake(); }
+                    if send.send(i).is_err() { break; }
+                }
+                return;
+            }
+            let mut state = shared.lock();
+            while !state.shutdown
             {
-                let res = task();
-                if send.send((i, key, res)).is_err() { break; }
+                let Some((key, task)) = state.pop() else { state = shared.queued.wait(state).unwrap_or_else(PoisonError::into_inner); continue };
+                state.running += 1;
+                drop(state);
+                let res = run(task);
+                state = shared.lock();
+                state.running -= 1;
+                let mut wakers: Vec<Waker> = state.finish(key, res).into_iter().collect();
+                wakers.extend(state.resolve_dependencies());
+                drop(state);
+                shared.notify(wakers, true);
+                state = shared.lock();
             }
         }).unwrap();
         Self { handle, submit: s1, free: true }
@@ -188,17 +654,51 @@ impl Worker
 impl ResSlot
 {
     fn free(&self) -> bool {  matches!(self, Self::Free) }
-    fn pending(&self) -> bool {  matches!(self, Self::Pending) }
 }
 
 impl<T> Clone for TaskKey<T>
 {
 	fn clone(&self) -> Self
 	{
-		Self { key: self.key, _phantom: PhantomData }
+		Self { key: self.key, shared: self.shared.clone(), _phantom: PhantomData }
 	}
 }
 
+impl<T> PartialEq for TaskKey<T>
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        self.key == other.key && Arc::ptr_eq(&self.shared, &other.shared)
+    }
+}
+
+impl<T> Eq for TaskKey<T> {}
+
+impl<T: 'static> Future for TaskKey<T>
+{
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>>
+    {
+        let mut state = self.shared.lock();
+        match state.take(self.key)
+        {
+            Query::Done(res) => Poll::Ready(Some(res)),
+            Query::InvalidKey => Poll::Ready(None),
+            Query::Panicked(payload) =>
+            {
+                drop(state);
+                panic::resume_unwind(payload)
+            },
+            Query::Pending =>
+            {
+                if let ResSlot::Pending(waker) = &mut state.slots[self.key.0].1 { *waker = Some(cx.waker().clone()); }
+                Poll::Pending
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests //cargo test --features thread -- --nocapture
 {
@@ -209,7 +709,7 @@ mod tests //cargo test --features thread -- --nocapture
     {
         fn check(pool: &mut Pool, keys: &mut Vec<(usize, TaskKey<usize>)>)
         {
-            if pool.available > 0
+            if pool.available_results() > 0
             {
                 for i in (0..keys.len()).rev()
                 {
@@ -252,7 +752,356 @@ mod tests //cargo test --features thread -- --nocapture
         let t_th = base_wait as usize * job_size_sqrt.pow(2) / pool_size;
         println!("finished in {}ms", t_tot);
         println!("minimum time ~{}ms", t_th);
-        println!("len res queue = {} vs {}", pool.res.len(), job_size_sqrt.pow(2));
+        println!("len res queue = {} vs {}", pool.shared.lock().slots.len(), job_size_sqrt.pow(2));
         if t_tot > 2 * t_th { panic!("execution takes too much time"); }
     }
+
+    #[test]
+    fn future_and_cancel()
+    {
+        struct Flag(std::sync::atomic::AtomicBool);
+
+        impl std::task::Wake for Flag
+        {
+            fn wake(self: Arc<Self>) { self.0.store(true, std::sync::atomic::Ordering::SeqCst); }
+        }
+
+        let mut pool = Pool::new(1);
+        let mut slow = pool.spawn(|| { thread::sleep(std::time::Duration::from_millis(100)); 1 });
+        let queued = pool.spawn(|| 2);
+        let done = pool.spawn(|| 3);
+        pool.poll();
+
+        //the worker wakes the awaiting key, the pool only dispatches
+        let flag = Arc::new(Flag(false.into()));
+        let waker = Waker::from(flag.clone());
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut slow).poll(&mut cx).is_pending());
+        assert!(pool.cancel(queued.clone()));
+        assert!(matches!(pool.query(queued), Query::InvalidKey));
+        while !flag.0.load(std::sync::atomic::Ordering::SeqCst) { pool.poll(); thread::yield_now(); }
+        assert_eq!(Pin::new(&mut slow.clone()).poll(&mut cx), Poll::Ready(Some(1)));
+        assert_eq!(Pin::new(&mut slow).poll(&mut cx), Poll::Ready(None));
+
+        //finished results of abandoned keys are reclaimed
+        while pool.available_results() == 0 { pool.poll(); thread::yield_now(); }
+        assert!(!pool.cancel(done.clone()));
+        assert_eq!(pool.available_results(), 0);
+        assert!(matches!(pool.query(done), Query::InvalidKey));
+        let slots = pool.shared.lock().slots.len();
+        let key = pool.spawn(|| 4);
+        assert_eq!(pool.shared.lock().slots.len(), slots);
+
+        //a running task that is cancelled frees its slot when it finishes
+        pool.poll();
+        pool.cancel(key.clone());
+        while pool.shared.lock().free.len() < slots { pool.poll(); thread::yield_now(); }
+        assert!(matches!(pool.query(key), Query::InvalidKey));
+    }
+
+    #[test]
+    fn drop_pool()
+    {
+        struct Flag(std::sync::atomic::AtomicBool);
+
+        impl std::task::Wake for Flag
+        {
+            fn wake(self: Arc<Self>) { self.0.store(true, std::sync::atomic::Ordering::SeqCst); }
+        }
+
+        for self_driving in [false, true]
+        {
+            let mut pool = if self_driving { Pool::self_driving(1) } else { Pool::new(1) };
+            let (gate, blocked) = std::sync::mpsc::channel::<()>();
+            let mut running = pool.spawn(move || blocked.recv().is_ok());
+            pool.poll();
+            while pool.idle_worker() > 0 { thread::yield_now(); }
+            let queued = pool.spawn(|| 1);
+            let waiting = pool.spawn_after(std::slice::from_ref(&queued), |results| results[0]);
+
+            //the keys of dropped tasks resolve to None, the running task still finishes
+            let flags: Vec<_> = (0..3).map(|_| Arc::new(Flag(false.into()))).collect();
+            let poll = |key: &mut TaskKey<_>, flag: &Arc<Flag>| Pin::new(key).poll(&mut Context::from_waker(&Waker::from(flag.clone())));
+            assert!(poll(&mut queued.clone(), &flags[0]).is_pending() && poll(&mut waiting.clone(), &flags[1]).is_pending());
+            assert!(Pin::new(&mut running).poll(&mut Context::from_waker(&Waker::from(flags[2].clone()))).is_pending());
+            drop(pool);
+            assert!(flags[0].0.load(std::sync::atomic::Ordering::SeqCst) && flags[1].0.load(std::sync::atomic::Ordering::SeqCst));
+            assert_eq!((poll(&mut queued.clone(), &flags[0]), poll(&mut waiting.clone(), &flags[1])), (Poll::Ready(None), Poll::Ready(None)));
+            gate.send(()).unwrap();
+            let start = std::time::Instant::now();
+            while !flags[2].0.load(std::sync::atomic::Ordering::SeqCst)
+            {
+                assert!(start.elapsed() < std::time::Duration::from_secs(10), "running task was not finished");
+                thread::yield_now();
+            }
+            assert_eq!(Pin::new(&mut running).poll(&mut Context::from_waker(Waker::noop())), Poll::Ready(Some(true)));
+        }
+    }
+
+    #[test]
+    fn panic()
+    {
+        let mut pool = Pool::new(1);
+        let faulty = pool.spawn(|| -> u32 { panic!("faulty job") });
+        let fine = pool.spawn(|| 5);
+        while pool.available_results() < 2 { pool.poll(); thread::yield_now(); }
+        match pool.query(faulty)
+        {
+            Query::Panicked(payload) => assert_eq!(payload.downcast_ref::<&str>(), Some(&"faulty job")),
+            _ => panic!("expected Query::Panicked")
+        }
+        assert_eq!(pool.query(fine).unwrap(), 5);
+
+        //the worker keeps running
+        let key = pool.spawn(|| 6);
+        while pool.available_results() < 1 { pool.poll(); thread::yield_now(); }
+        assert_eq!(pool.query(key).unwrap(), 6);
+        pool.poll();
+        assert_eq!(pool.idle_worker(), 1);
+    }
+
+    #[test]
+    fn priorities_and_dependencies()
+    {
+        let mut pool = Pool::new(1);
+        let (gate, blocked) = std::sync::mpsc::channel::<()>();
+        let blocker = pool.spawn(move || blocked.recv().ok());
+        pool.poll();
+
+        //queued behind the blocker, dispatched by priority
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut keys = Vec::new();
+        for (id, priority) in [(0, Priority::Low), (1, Priority::Normal), (2, Priority::High), (3, Priority::Normal), (4, Priority::High)]
+        {
+            let order = order.clone();
+            keys.push(pool.spawn_with_priority(priority, move || order.lock().unwrap().push(id)));
+        }
+        gate.send(()).unwrap();
+        while pool.available_results() < 6 { pool.poll(); thread::yield_now(); }
+        assert_eq!(*order.lock().unwrap(), [2, 4, 1, 3, 0]);
+        for key in keys { pool.query(key).unwrap(); }
+        pool.query(blocker).unwrap();
+
+        //results are passed in the order of the keys, chains resolve as their dependencies finish
+        let a = pool.spawn(|| { thread::sleep(std::time::Duration::from_millis(20)); 2 });
+        let b = pool.spawn(|| 3);
+        let c = pool.spawn_after(&[a.clone(), b], |results| results[0] * 10 + results[1]);
+        let d = pool.spawn_after_with_priority(Priority::High, std::slice::from_ref(&c), |results| results[0] + 1);
+        let faulty = pool.spawn(|| -> i32 { panic!("faulty dependency") });
+        let failed = pool.spawn_after(&[faulty], |results| results.len());
+        let cancelled = pool.spawn(|| 0);
+        pool.cancel(cancelled.clone());
+        let orphan = pool.spawn_after(&[cancelled], |results| results.len());
+        let d = loop
+        {
+            pool.poll();
+            match pool.query(d.clone())
+            {
+                Query::Pending => thread::yield_now(),
+                res => break res.unwrap()
+            }
+        };
+        assert_eq!(d, 24);
+        assert!(matches!(pool.query(a), Query::InvalidKey));
+        assert!(matches!(pool.query(c), Query::InvalidKey));
+        assert!(matches!(pool.query(failed), Query::Panicked(_)));
+        assert!(matches!(pool.query(orphan), Query::InvalidKey));
+        assert_eq!(pool.available_results(), 0);
+
+        //repeated keys and keys of other pools cancel the task without consuming any result
+        let a = pool.spawn(|| 1);
+        let mut other = Pool::new(1);
+        let foreign = other.spawn(|| 2);
+        let repeated = pool.spawn_after(&[a.clone(), a.clone()], |results| results.len());
+        let mixed = pool.spawn_after(&[a.clone(), foreign.clone()], |results| results.len());
+        assert!(matches!(pool.wait(repeated), Query::InvalidKey));
+        assert!(matches!(pool.wait(mixed), Query::InvalidKey));
+        assert!(matches!(pool.query(foreign.clone()), Query::InvalidKey));
+        assert!(!pool.cancel(foreign.clone()));
+        assert_eq!(pool.wait(a).unwrap(), 1);
+        assert_eq!(other.wait(foreign).unwrap(), 2);
+        let key = pool.spawn(|| 3);
+        assert_eq!(pool.wait(key).unwrap(), 3);
+        assert_eq!(pool.available_results(), 0);
+    }
+
+    #[test]
+    fn self_driving_and_wait()
+    {
+        for self_driving in [false, true]
+        {
+            let mut pool = if self_driving { Pool::self_driving(4) } else { Pool::new(4) };
+            let keys: Vec<_> = (0..16).map(|i: i32| pool.spawn(move || { thread::sleep(std::time::Duration::from_millis(10)); i })).collect();
+            let sum = pool.spawn_after(&keys[8..], |results| results.into_iter().sum::<i32>());
+            for (i, key) in keys.into_iter().take(8).enumerate() { assert_eq!(pool.wait(key).unwrap(), i as i32); }
+            assert_eq!(pool.wait(sum.clone()).unwrap(), (8..16).sum::<i32>());
+            assert!(matches!(pool.wait(sum), Query::InvalidKey));
+
+            let faulty = pool.spawn(|| -> u8 { panic!("faulty job") });
+            let keys: Vec<_> = (0..8).map(|i| pool.spawn_with_priority(Priority::Low, move || i * 2)).collect();
+            pool.wait_all();
+            assert_eq!(pool.idle_worker(), 4);
+            assert_eq!(pool.available_results(), 9);
+            assert!(matches!(pool.wait(faulty), Query::Panicked(_)));
+            for (i, key) in keys.into_iter().enumerate() { assert_eq!(pool.query(key).unwrap(), i * 2); }
+        }
+
+        //tasks reach the workers without anyone polling the pool
+        let mut pool = Pool::self_driving(2);
+        let key = pool.spawn(|| 7);
+        let start = std::time::Instant::now();
+        while pool.available_results() == 0
+        {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10), "task was not dispatched");
+            thread::yield_now();
+        }
+        let waker = Waker::noop();
+        assert_eq!(Pin::new(&mut key.clone()).poll(&mut Context::from_waker(waker)), Poll::Ready(Some(7)));
+        pool.shutdown().join();
+    }
+
+    #[test]
+    fn no_workers()
+    {
+        for self_driving in [false, true]
+        {
+            let mut pool = if self_driving { Pool::self_driving(0) } else { Pool::new(0) };
+            let keys: Vec<_> = (0..4).map(|i: i32| pool.spawn(move || i)).collect();
+            let sum = pool.spawn_after(&keys[2..], |results| results.into_iter().sum::<i32>());
+            let faulty = pool.spawn(|| -> u8 { panic!("faulty job") });
+            assert!(matches!(pool.query(keys[0].clone()), Query::Pending));
+            assert_eq!(pool.wait(sum).unwrap(), 5);
+            assert!(matches!(pool.wait(faulty), Query::Panicked(_)));
+            pool.wait_all();
+            assert_eq!(pool.available_results(), 2);
+            for (i, key) in keys.into_iter().take(2).enumerate() { assert_eq!(pool.query(key).unwrap(), i as i32); }
+            //polling runs the tasks on the calling thread
+            let late = pool.spawn_with_priority(Priority::Low, || thread::current().id());
+            pool.poll();
+            assert_eq!(pool.query(late).unwrap(), thread::current().id());
+        }
+    }
+
+    #[test]
+    fn scope_and_par()
+    {
+        for self_driving in [false, true]
+        {
+            let mut pool = if self_driving { Pool::self_driving(4) } else { Pool::new(4) };
+            let mut pixels = [0u32; 1000];
+            let mut halves = pixels.split_at_mut(500);
+            let offset = 7;
+            let sum = pool.scope(|s|
+            {
+                let (left, right) = &mut halves;
+                s.spawn(|| for (i, pixel) in left.iter_mut().enumerate() { *pixel = i as u32 + offset; });
+                s.spawn(|| for (i, pixel) in right.iter_mut().enumerate() { *pixel = (500 + i) as u32 + offset; });
+                3
+            });
+            assert_eq!(sum, 3);
+            assert!(pixels.iter().enumerate().all(|(i, &pixel)| pixel == i as u32 + offset));
+
+            pool.par_chunks_mut(&mut pixels, 33, |index, chunk|
+            {
+                assert!(chunk.len() == 33 || index == 1000 / 33);
+                for pixel in chunk { *pixel *= 2; }
+            });
+            assert!(pixels.iter().enumerate().all(|(i, &pixel)| pixel == (i as u32 + offset) * 2));
+
+            let visited: Vec<_> = (0..100).map(|_| std::sync::atomic::AtomicUsize::new(0)).collect();
+            pool.par_for(0..100, |i| { visited[i].fetch_add(i, std::sync::atomic::Ordering::Relaxed); });
+            assert!(visited.iter().enumerate().all(|(i, count)| count.load(std::sync::atomic::Ordering::Relaxed) == i));
+            pool.par_for(5..5, |_| unreachable!());
+
+            //the panic is resumed after the other tasks finished, the pool keeps working
+            let mut finished = false;
+            let res = panic::catch_unwind(panic::AssertUnwindSafe(|| pool.scope(|s|
+            {
+                s.spawn(|| panic!("faulty job"));
+                s.spawn(|| { thread::sleep(std::time::Duration::from_millis(20)); finished = true; });
+            })));
+            assert!(res.is_err() && finished);
+            assert_eq!(pool.available_results(), 0);
+            let key = pool.spawn(|| 1);
+            assert_eq!(pool.wait(key).unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn scope_panics()
+    {
+        let sleep = || thread::sleep(std::time::Duration::from_millis(20));
+        for workers in [0, 1]
+        {
+            for self_driving in [false, true]
+            {
+                let mut pool = if self_driving { Pool::self_driving(workers) } else { Pool::new(workers) };
+                //the only worker is busy, the panicking task runs before its sibling has started
+                let busy = pool.spawn(sleep);
+                let mut written = Vec::new();
+                let res = panic::catch_unwind(panic::AssertUnwindSafe(|| pool.scope(|s|
+                {
+                    s.spawn(|| panic!("faulty job"));
+                    s.spawn(|| { sleep(); written.push(1); });
+                })));
+                assert!(res.is_err());
+                assert_eq!(written, [1]);
+
+                //a panic of the closure itself waits for the tasks spawned before it
+                let res = panic::catch_unwind(panic::AssertUnwindSafe(|| pool.scope(|s|
+                {
+                    s.spawn(|| { sleep(); written.push(2); });
+                    panic!("faulty scope");
+                })));
+                assert!(res.is_err());
+                assert_eq!(written, [1, 2]);
+                pool.wait(busy).unwrap();
+
+                let mut data = [1u32; 100];
+                pool.par_chunks_mut(&mut data, 7, |index, chunk| for value in chunk { *value += index as u32; });
+                assert!(data.iter().enumerate().all(|(i, &value)| value == 1 + i as u32 / 7));
+                let sum = std::sync::atomic::AtomicUsize::new(0);
+                pool.par_for(0..100, |i| { sum.fetch_add(i, std::sync::atomic::Ordering::Relaxed); });
+                assert_eq!(sum.into_inner(), 4950);
+                assert_eq!(pool.available_results(), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn scope_pool_panics()
+    {
+        struct Faulty;
+
+        impl std::task::Wake for Faulty
+        {
+            fn wake(self: Arc<Self>) { panic!("faulty waker"); }
+        }
+
+        let sleep = |millis| thread::sleep(std::time::Duration::from_millis(millis));
+        //polling the pool panics in the waker of a failed dependent while the scoped task is running
+        let mut pool = Pool::new(2);
+        let faulty = pool.spawn(move || -> u8 { sleep(20); panic!("faulty job") });
+        let mut failed = pool.spawn_after(&[faulty], |results| results.len());
+        let waker = Waker::from(Arc::new(Faulty));
+        assert!(Pin::new(&mut failed).poll(&mut Context::from_waker(&waker)).is_pending());
+        let mut written = Vec::new();
+        let res = panic::catch_unwind(panic::AssertUnwindSafe(|| pool.scope(|s| s.spawn(|| { sleep(100); written.push(1); }))));
+        assert_eq!(res.unwrap_err().downcast_ref::<&str>(), Some(&"faulty waker"));
+        assert_eq!(written, [1]);
+        assert!(matches!(pool.query(failed), Query::Panicked(_)));
+
+        //a lock poisoned by a panic does not take the pool down
+        for self_driving in [false, true]
+        {
+            let mut pool = if self_driving { Pool::self_driving(2) } else { Pool::new(2) };
+            let shared = pool.shared.clone();
+            assert!(thread::spawn(move || { let _state = shared.lock(); panic!("poisoning") }).join().is_err());
+            let mut written = Vec::new();
+            let res = pool.scope(|s| { s.spawn(|| { sleep(20); written.push(2); }); 3 });
+            assert_eq!((res, written), (3, vec![2]));
+            let key = pool.spawn(|| 4);
+            assert_eq!(pool.wait(key).unwrap(), 4);
+        }
+    }
 }